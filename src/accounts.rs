@@ -0,0 +1,225 @@
+use std::{env, fmt, sync::{Arc, Mutex}};
+
+use argon2::{password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString}, Argon2};
+use rand::Rng;
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension};
+use serde::Serialize;
+
+/// Chips credited to a freshly registered account
+const STARTING_BANKROLL: i64 = 1000;
+
+#[derive(Serialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AccountError {
+    NameTaken,
+    InvalidCredentials,
+    AccountNotFound,
+    InsufficientBankroll,
+    Database,
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            AccountError::NameTaken => "An account with this name already exists",
+            AccountError::InvalidCredentials => "Wrong name or password",
+            AccountError::AccountNotFound => "The account does not exist",
+            AccountError::InsufficientBankroll => "Not enough chips in the bankroll",
+            AccountError::Database => "Database error",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+impl From<rusqlite::Error> for AccountError {
+    fn from(error: rusqlite::Error) -> Self {
+        println!("Database error: {}", error);
+        AccountError::Database
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Account {
+    pub id: i64,
+    pub name: String,
+    pub bankroll: i64,
+}
+
+/// Player accounts and their bankrolls, persisted in a SQLite file
+pub struct Accounts {
+    connection: Mutex<Connection>,
+}
+
+impl Accounts {
+    pub fn open(path: &str) -> Result<Self, rusqlite::Error> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS accounts (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                password_hash TEXT NOT NULL,
+                bankroll INTEGER NOT NULL CHECK (bankroll >= 0)
+            );
+            CREATE TABLE IF NOT EXISTS open_stacks (
+                key TEXT PRIMARY KEY,
+                account INTEGER NOT NULL REFERENCES accounts (id),
+                chips INTEGER NOT NULL
+            );"
+        )?;
+
+        Ok(Self { connection: Mutex::new(connection) })
+    }
+
+    /// Opens the database at `DATABASE_PATH`, `poker.db` by default
+    pub fn from_env() -> Result<Self, rusqlite::Error> {
+        Self::open(&env::var("DATABASE_PATH").unwrap_or("poker.db".to_string()))
+    }
+
+    /// Runs `work` on the blocking thread pool, SQLite calls and password hashing would stall the async runtime
+    pub async fn run<T: Send + 'static>(self: &Arc<Self>, work: impl FnOnce(&Accounts) -> Result<T, AccountError> + Send + 'static) -> Result<T, AccountError> {
+        let accounts = self.clone();
+        tokio::task::spawn_blocking(move || work(&accounts)).await.unwrap_or(Err(AccountError::Database))
+    }
+
+    pub fn register(&self, name: &str, password: &str) -> Result<Account, AccountError> {
+        let salt = SaltString::encode_b64(&rand::rng().random::<[u8; 16]>()).unwrap();
+        let password_hash = Argon2::default().hash_password(password.as_bytes(), &salt).unwrap().to_string();
+
+        let connection = self.connection.lock().unwrap();
+        let result = connection.execute(
+            "INSERT INTO accounts (name, password_hash, bankroll) VALUES (?1, ?2, ?3)",
+            params![name, password_hash, STARTING_BANKROLL],
+        );
+        match result {
+            Err(rusqlite::Error::SqliteFailure(error, _)) if error.code == ErrorCode::ConstraintViolation => {
+                return Err(AccountError::NameTaken);
+            }
+            result => { result?; }
+        }
+
+        Ok(Account { id: connection.last_insert_rowid(), name: name.to_string(), bankroll: STARTING_BANKROLL })
+    }
+
+    pub fn login(&self, name: &str, password: &str) -> Result<Account, AccountError> {
+        let row = self.connection.lock().unwrap().query_row(
+            "SELECT id, password_hash, bankroll FROM accounts WHERE name = ?1",
+            params![name],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?)),
+        ).optional()?;
+        let Some((id, password_hash, bankroll)) = row else { return Err(AccountError::InvalidCredentials); };
+
+        let password_hash = PasswordHash::new(&password_hash).map_err(|_| AccountError::Database)?;
+        if Argon2::default().verify_password(password.as_bytes(), &password_hash).is_err() {
+            return Err(AccountError::InvalidCredentials);
+        }
+
+        Ok(Account { id, name: name.to_string(), bankroll })
+    }
+
+    pub fn get_account(&self, id: i64) -> Result<Account, AccountError> {
+        self.connection.lock().unwrap().query_row(
+            "SELECT id, name, bankroll FROM accounts WHERE id = ?1",
+            params![id],
+            |row| Ok(Account { id: row.get(0)?, name: row.get(1)?, bankroll: row.get(2)? }),
+        ).optional()?.ok_or(AccountError::AccountNotFound)
+    }
+
+    /// Takes `amount` chips out of the bankroll, fails without touching it when there aren't enough
+    pub fn withdraw(&self, id: i64, amount: i64) -> Result<(), AccountError> {
+        let updated = self.connection.lock().unwrap().execute(
+            "UPDATE accounts SET bankroll = bankroll - ?1 WHERE id = ?2 AND bankroll >= ?1",
+            params![amount, id],
+        )?;
+        if updated == 0 {
+            self.get_account(id)?;
+            return Err(AccountError::InsufficientBankroll);
+        }
+
+        Ok(())
+    }
+
+    pub fn deposit(&self, id: i64, amount: i64) -> Result<(), AccountError> {
+        let updated = self.connection.lock().unwrap().execute(
+            "UPDATE accounts SET bankroll = bankroll + ?1 WHERE id = ?2",
+            params![amount, id],
+        )?;
+        if updated == 0 { return Err(AccountError::AccountNotFound); }
+
+        Ok(())
+    }
+
+    /// Records the chips a player brought to a table under their session key, until `close_stack`
+    pub fn open_stack(&self, id: i64, key: &str, chips: i64) -> Result<(), AccountError> {
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO open_stacks (key, account, chips) VALUES (?1, ?2, ?3)",
+            params![key, id, chips],
+        )?;
+
+        Ok(())
+    }
+
+    /// Saves the current size of open stacks, so they can be refunded if the server stops
+    pub fn update_stacks(&self, stacks: &[(String, i64)]) -> Result<(), AccountError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for (key, chips) in stacks {
+            transaction.execute("UPDATE open_stacks SET chips = ?1 WHERE key = ?2", params![chips, key])?;
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Cashes out the chips a player leaves the table with
+    pub fn close_stack(&self, id: i64, key: &str, chips: i64) -> Result<(), AccountError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let updated = transaction.execute("UPDATE accounts SET bankroll = bankroll + ?1 WHERE id = ?2", params![chips, id])?;
+        if updated == 0 { return Err(AccountError::AccountNotFound); }
+        transaction.execute("DELETE FROM open_stacks WHERE key = ?1", params![key])?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Gives back the stacks of the players who were still seated when the server stopped,
+    /// returns how many were refunded
+    pub fn refund_open_stacks(&self) -> Result<usize, AccountError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "UPDATE accounts SET bankroll = bankroll + (SELECT SUM(chips) FROM open_stacks WHERE account = accounts.id)
+            WHERE id IN (SELECT account FROM open_stacks)",
+            [],
+        )?;
+        let refunded = transaction.execute("DELETE FROM open_stacks", [])?;
+        transaction.commit()?;
+
+        Ok(refunded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_stacks_are_refunded_after_a_restart() {
+        let accounts = Accounts::open(":memory:").unwrap();
+        let alice = accounts.register("alice", "password").unwrap().id;
+        let bob = accounts.register("bob", "password").unwrap().id;
+
+        for (id, key) in [(alice, "a"), (bob, "b")] {
+            accounts.withdraw(id, 500).unwrap();
+            accounts.open_stack(id, key, 500).unwrap();
+        }
+        accounts.update_stacks(&[("a".to_string(), 700), ("b".to_string(), 300)]).unwrap();
+        accounts.close_stack(bob, "b", 300).unwrap();
+
+        assert_eq!(accounts.refund_open_stacks(), Ok(1));
+        assert_eq!(accounts.get_account(alice).unwrap().bankroll, 1200);
+        assert_eq!(accounts.get_account(bob).unwrap().bankroll, 800);
+        assert_eq!(accounts.refund_open_stacks(), Ok(0));
+    }
+}
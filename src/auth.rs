@@ -0,0 +1,212 @@
+use std::{env, sync::{Arc, Mutex}};
+
+use axum::{extract::{FromRef, FromRequestParts}, http::{header::AUTHORIZATION, request::Parts, StatusCode}};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{accounts::Accounts, engine::Engine};
+
+/// Tokens stay valid for 12 hours unless `JWT_EXPIRY_SECONDS` says otherwise
+const DEFAULT_EXPIRY_SECONDS: i64 = 12 * 60 * 60;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub engine: Arc<Mutex<Engine>>,
+    pub auth: Arc<Auth>,
+    pub accounts: Arc<Accounts>,
+}
+
+impl FromRef<AppState> for Arc<Mutex<Engine>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.engine.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Auth> {
+    fn from_ref(state: &AppState) -> Self {
+        state.auth.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Accounts> {
+    fn from_ref(state: &AppState) -> Self {
+        state.accounts.clone()
+    }
+}
+
+pub struct Auth {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    validity: Duration,
+}
+
+/// Payload of the tokens handed out on create/join.
+/// `key` is the player's session key, `table` and `seat` are where they sat when the token was issued.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Claims {
+    pub key: String,
+    pub account: i64,
+    pub name: String,
+    pub table: u64,
+    pub seat: usize,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Payload of the tokens handed out on register/login, needed to buy into a table.
+/// Unknown fields are rejected so a table token, which has more, can't be used as an account token.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AccountClaims {
+    pub account: i64,
+    pub name: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+impl Auth {
+    pub fn new(secret: &[u8], validity: Duration) -> Self {
+        Self {
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            validity,
+        }
+    }
+
+    /// Reads the signing secret from `JWT_SECRET` and the token lifetime from `JWT_EXPIRY_SECONDS`.
+    /// Without a secret a random one is generated, so tokens don't survive a restart.
+    pub fn from_env() -> Self {
+        let secret = match env::var("JWT_SECRET") {
+            Ok(secret) => secret.into_bytes(),
+            Err(_) => {
+                println!("JWT_SECRET is not set, using a random secret");
+                rand::rng().random::<[u8; 32]>().to_vec()
+            }
+        };
+        let expiry_seconds = env::var("JWT_EXPIRY_SECONDS").ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(DEFAULT_EXPIRY_SECONDS);
+
+        Self::new(&secret, Duration::seconds(expiry_seconds))
+    }
+
+    pub fn issue_token(&self, key: String, account: i64, name: String, table: u64, seat: usize) -> String {
+        let now = Utc::now();
+        let claims = Claims {
+            key,
+            account,
+            name,
+            table,
+            seat,
+            iat: now.timestamp(),
+            exp: (now + self.validity).timestamp(),
+        };
+
+        encode(&Header::default(), &claims, &self.encoding_key).unwrap()
+    }
+
+    pub fn issue_account_token(&self, account: i64, name: String) -> String {
+        let now = Utc::now();
+        let claims = AccountClaims {
+            account,
+            name,
+            iat: now.timestamp(),
+            exp: (now + self.validity).timestamp(),
+        };
+
+        encode(&Header::default(), &claims, &self.encoding_key).unwrap()
+    }
+
+    /// Checks the signature and expiry of `token`
+    pub fn verify_token<T: DeserializeOwned>(&self, token: &str) -> Option<T> {
+        decode::<T>(token, &self.decoding_key, &Validation::default())
+            .ok()
+            .map(|data| data.claims)
+    }
+
+    /// Checks the signature of `token` but not its expiry, only for trading it for a fresh token
+    pub fn verify_signature<T: DeserializeOwned>(&self, token: &str) -> Option<T> {
+        let mut validation = Validation::default();
+        validation.validate_exp = false;
+        decode::<T>(token, &self.decoding_key, &validation)
+            .ok()
+            .map(|data| data.claims)
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts.headers.get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Extracts and verifies the `Authorization: Bearer <token>` header
+impl<S> FromRequestParts<S> for Claims
+where
+    Arc<Auth>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts).ok_or(StatusCode::UNAUTHORIZED)?;
+
+        Arc::<Auth>::from_ref(state).verify_token(token).ok_or(StatusCode::UNAUTHORIZED)
+    }
+}
+
+impl<S> FromRequestParts<S> for AccountClaims
+where
+    Arc<Auth>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts).ok_or(StatusCode::UNAUTHORIZED)?;
+
+        Arc::<Auth>::from_ref(state).verify_token(token).ok_or(StatusCode::UNAUTHORIZED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_and_account_tokens_are_not_interchangeable() {
+        let auth = Auth::new(b"secret", Duration::hours(1));
+        let table_token = auth.issue_token("key".to_string(), 1, "alice".to_string(), 0, 0);
+        let account_token = auth.issue_account_token(1, "alice".to_string());
+
+        assert!(auth.verify_token::<Claims>(&table_token).is_some());
+        assert!(auth.verify_token::<AccountClaims>(&table_token).is_none());
+        assert!(auth.verify_token::<AccountClaims>(&account_token).is_some());
+        assert!(auth.verify_token::<Claims>(&account_token).is_none());
+    }
+
+    #[test]
+    fn tampered_and_expired_tokens_are_rejected() {
+        let auth = Auth::new(b"secret", Duration::hours(1));
+        let token = auth.issue_token("key".to_string(), 1, "alice".to_string(), 0, 0);
+
+        let forged = Auth::new(b"other secret", Duration::hours(1)).issue_token("key".to_string(), 1, "alice".to_string(), 0, 0);
+        assert!(auth.verify_token::<Claims>(&forged).is_none());
+        assert!(auth.verify_signature::<Claims>(&forged).is_none());
+
+        // Same header and signature over a payload claiming another seat
+        let (payload, signature) = token.rsplit_once('.').unwrap();
+        let other = auth.issue_token("key".to_string(), 1, "alice".to_string(), 0, 1);
+        let tampered = format!("{}.{}", other.rsplit_once('.').unwrap().0, signature);
+        assert_ne!(payload, other.rsplit_once('.').unwrap().0);
+        assert!(auth.verify_token::<Claims>(&tampered).is_none());
+        assert!(auth.verify_signature::<Claims>(&tampered).is_none());
+
+        // Past the default 60 seconds of leeway
+        let expired = Auth::new(b"secret", Duration::minutes(-5)).issue_token("key".to_string(), 1, "alice".to_string(), 0, 0);
+        assert!(auth.verify_token::<Claims>(&expired).is_none());
+        assert_eq!(auth.verify_signature::<Claims>(&expired).map(|claims| claims.key), Some("key".to_string()));
+    }
+}
@@ -1,10 +1,10 @@
-use std::{cmp::Ordering, collections::HashMap};
+use std::{cmp::Ordering, fmt, str::FromStr};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-#[derive(Serialize, Copy, Clone, EnumIter, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, EnumIter, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 pub enum Color {
     Heart,
     Diamond,
@@ -12,7 +12,7 @@ pub enum Color {
     Spade
 }
 
-#[derive(Serialize, Copy, Clone, EnumIter, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, EnumIter, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 pub enum Rank {
     Two,
     Three,
@@ -30,12 +30,172 @@ pub enum Rank {
 }
 
 
-#[derive(Serialize, Copy, Clone, PartialEq, Debug)]
+/// Serializes as `{"color": "Heart", "rank": "Ace"}`, see `compact` for the "Ah" notation
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
 pub struct Card {
     pub color: Color,
     pub rank: Rank
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ParseCardError {
+    InvalidRank,
+    InvalidColor,
+    /// A card is a rank followed by a color, like "Ah" or "Td"
+    InvalidLength,
+}
+
+impl fmt::Display for ParseCardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ParseCardError::InvalidRank => "Rank must be one of 23456789TJQKA",
+            ParseCardError::InvalidColor => "Color must be one of hdcs",
+            ParseCardError::InvalidLength => "A card is a rank followed by a color, like Ah",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for ParseCardError {}
+
+impl Rank {
+    fn to_char(self) -> char {
+        match self {
+            Rank::Two => '2',
+            Rank::Three => '3',
+            Rank::Four => '4',
+            Rank::Five => '5',
+            Rank::Six => '6',
+            Rank::Seven => '7',
+            Rank::Eight => '8',
+            Rank::Nine => '9',
+            Rank::Ten => 'T',
+            Rank::Jack => 'J',
+            Rank::Queen => 'Q',
+            Rank::King => 'K',
+            Rank::Ace => 'A',
+        }
+    }
+
+    fn from_char(c: char) -> Result<Self, ParseCardError> {
+        Rank::iter().find(|rank| rank.to_char() == c.to_ascii_uppercase()).ok_or(ParseCardError::InvalidRank)
+    }
+}
+
+impl Color {
+    fn to_char(self) -> char {
+        match self {
+            Color::Heart => 'h',
+            Color::Diamond => 'd',
+            Color::Club => 'c',
+            Color::Spade => 's',
+        }
+    }
+
+    fn from_char(c: char) -> Result<Self, ParseCardError> {
+        Color::iter().find(|color| color.to_char() == c.to_ascii_lowercase()).ok_or(ParseCardError::InvalidColor)
+    }
+}
+
+/// Parses a single character, "A" or "a" for an ace
+impl FromStr for Rank {
+    type Err = ParseCardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Rank::from_char(c),
+            _ => Err(ParseCardError::InvalidLength),
+        }
+    }
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+/// Parses a single character, "h" or "H" for hearts
+impl FromStr for Color {
+    type Err = ParseCardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Color::from_char(c),
+            _ => Err(ParseCardError::InvalidLength),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+/// Parses the rank followed by the color, "Ah" or "Td"
+impl FromStr for Card {
+    type Err = ParseCardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(rank), Some(color), None) => Ok(Card { rank: Rank::from_char(rank)?, color: Color::from_char(color)? }),
+            _ => Err(ParseCardError::InvalidLength),
+        }
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.rank, self.color)
+    }
+}
+
+/// Parses a list of cards, with or without whitespace between them: "AhKd Qs"
+pub fn parse_cards(text: &str) -> Result<Vec<Card>, ParseCardError> {
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.chunks(2).map(|card| match card {
+        &[rank, color] => Ok(Card { rank: Rank::from_char(rank)?, color: Color::from_char(color)? }),
+        _ => Err(ParseCardError::InvalidLength),
+    }).collect()
+}
+
+/// Serializes a card as "Ah" instead of its fields, for use with `#[serde(with = "compact")]`
+pub mod compact {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::Card;
+
+    pub fn serialize<S: Serializer>(card: &Card, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(card)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Card, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(D::Error::custom)
+    }
+
+    /// Serializes a list of cards as a single string, "Ah Kd Qs"
+    pub mod list {
+        use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+        use super::super::{parse_cards, Card};
+
+        pub fn serialize<S: Serializer>(cards: &[Card], serializer: S) -> Result<S::Ok, S::Error> {
+            let cards: Vec<String> = cards.iter().map(Card::to_string).collect();
+            serializer.serialize_str(&cards.join(" "))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Card>, D::Error> {
+            let text = String::deserialize(deserializer)?;
+            parse_cards(&text).map_err(D::Error::custom)
+        }
+    }
+}
+
 
 #[derive(PartialEq, Eq, Copy, Clone, PartialOrd, Ord, Serialize, Debug)]
 pub enum HandType {
@@ -50,7 +210,7 @@ pub enum HandType {
     StraightFlush,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone, Debug)]
 pub struct FullHand {
     pub hand_type: HandType,
     pub ranks: Vec<Rank>,
@@ -61,259 +221,607 @@ pub fn get_new_deck() -> Vec<Card> {
     let mut vec = Vec::new();
     for color in Color::iter() {
         for rank in Rank::iter() {
-            vec.push(Card { color: color.clone(), rank })
+            vec.push(Card { color, rank })
         }
     }
 
     vec
 }
 
+/// Calls `visit` with every `size`-card combination of `deck`, in lexicographic order of positions
+pub fn for_each_combination(deck: &[Card], size: usize, mut visit: impl FnMut(&[Card])) {
+    let mut indices: Vec<usize> = (0..size).collect();
+    let mut hand: Vec<Card> = indices.iter().map(|&i| deck[i]).collect();
+    loop {
+        visit(&hand);
+
+        let Some(i) = (0..size).rev().find(|&i| indices[i] != i + deck.len() - size) else { return; };
+        indices[i] += 1;
+        for j in i + 1..size {
+            indices[j] = indices[j - 1] + 1;
+        }
+        for j in i..size {
+            hand[j] = deck[indices[j]];
+        }
+    }
+}
+
 pub fn compare_hands(hand1: &FullHand, hand2: &FullHand) -> Ordering {
-    // use HandType::*;
-
-    for (a, b) in hand1.ranks.iter().zip(hand2.ranks.iter()) {
-        let ord = a.cmp(b);
-        if ord != Ordering::Equal {
-            return ord;
-        }
-    }
-
-    Ordering::Equal    
-    // match (hand1.hand_type, hand2.hand_type) {
-    //     (StraightFlush, StraightFlush) => hand1.ranks[0].cmp(&hand2.ranks[0]),
-    //     (FourOfAKind, FourOfAKind) => hand1.ranks[0].cmp(&hand2.ranks[0]).then(hand1.ranks[1].cmp(&hand2.ranks[1])),
-    //     (FullHouse, FullHouse) => hand1.ranks[0].cmp(&hand2.ranks[0]).then(hand1.ranks[1].cmp(&hand2.ranks[1])),
-    //     (Flush, Flush) => compare_rank_lists(&hand1.ranks, &hand2.ranks),
-    //     (Straight, Straight) => hand1.ranks[0].cmp(&hand2.ranks[0]),
-    //     (ThreeOfAKind, ThreeOfAKind) => hand1.ranks[0].cmp(&hand2.ranks[0]).then(compare_rank_lists(&hand1.ranks, &hand2.ranks)),
-    //     (TwoPair, TwoPair) => hand1.ranks[0].cmp(&hand2.ranks[0]).then(hand1.ranks[1].cmp(&hand2.ranks[1])).then(hand1.ranks[2].cmp(&hand2.ranks[2])),
-    //     (OnePair, OnePair) => hand1.ranks[0].cmp(&hand2.ranks[0]).then(compare_rank_lists(&hand1.ranks, &hand2.ranks)),
-    //     (HighCard, OnePair) => hand1.ranks[0].cmp(&hand2.ranks[0]).then(compare_rank_lists(&hand1.ranks, &hand2.ranks)),
-    //     _ => compare_rank_lists(&hand1.ranks, &hand2.ranks)
-    // }
+    hand1.hand_type.cmp(&hand2.hand_type).then_with(|| hand1.ranks.cmp(&hand2.ranks))
 }
 
-// fn compare_rank_lists(r1: &Vec<Rank>, r2: &Vec<Rank>) -> Ordering{
-//     for (a, b) in r1.iter().zip(r2.iter()) {
-//         let ord = a.cmp(b);
-//         if ord != Ordering::Equal {
-//             return ord;
-//         }
-//     }
+pub fn get_best_hand(cards: [Card; 7]) -> FullHand {
+    evaluate(&cards).to_full_hand()
+}
 
-//     Ordering::Equal
-// }
+const RANKS: [Rank; 13] = [
+    Rank::Two, Rank::Three, Rank::Four, Rank::Five, Rank::Six, Rank::Seven, Rank::Eight,
+    Rank::Nine, Rank::Ten, Rank::Jack, Rank::Queen, Rank::King, Rank::Ace,
+];
+
+const HAND_TYPES: [HandType; 9] = [
+    HandType::HighCard, HandType::OnePair, HandType::TwoPair, HandType::ThreeOfAKind, HandType::Straight,
+    HandType::Flush, HandType::FullHouse, HandType::FourOfAKind, HandType::StraightFlush,
+];
+
+/// Highest rank of the best straight in each 13-bit rank mask, plus one, 0 when there is none
+static STRAIGHT_HIGHS: [u8; 8192] = {
+    let mut table = [0; 8192];
+    let mut mask = 0;
+    while mask < 8192 {
+        // Bit 0 stands for the ace playing low, bits 1 to 13 for the ranks Two to Ace
+        let ranks = (mask << 1) | (mask >> 12);
+        let runs = ranks & (ranks >> 1) & (ranks >> 2) & (ranks >> 3) & (ranks >> 4);
+        if runs != 0 {
+            table[mask] = (usize::BITS - runs.leading_zeros() + 3) as u8;
+        }
+        mask += 1;
+    }
+    table
+};
+
+/// Five highest ranks of each 13-bit rank mask, packed four bits each with the highest in bits 16 to 19
+static HIGHEST_RANKS: [u32; 8192] = {
+    let mut table = [0; 8192];
+    let mut mask = 0;
+    while mask < 8192 {
+        let mut packed = 0;
+        let mut taken = 0;
+        let mut rank = 13;
+        while rank > 0 && taken < 5 {
+            rank -= 1;
+            if mask & (1 << rank) != 0 {
+                packed |= (rank as u32) << (16 - 4 * taken);
+                taken += 1;
+            }
+        }
+        table[mask] = packed;
+        mask += 1;
+    }
+    table
+};
 
-pub fn get_best_hand(cards: [Card; 7]) -> FullHand {
-    let mut color_counts = HashMap::new();
-    let mut rank_counts = HashMap::new();
-    
-    let mut ranks: Vec<Rank> = cards.iter().map(|c| c.rank.clone()).collect();
-    ranks.sort();
-    ranks.reverse();
-
-    for card in &cards {
-        *rank_counts.entry(&card.rank).or_insert(0) += 1 as usize;
-        color_counts.entry(&card.color).or_insert(Vec::new()).push(&card.rank);
-    }
-
-    if let Some(high) = find_straight_flush(&color_counts) {
-        return FullHand {
-            hand_type: HandType::StraightFlush,
-            ranks: vec![high]
-        };
+/// Strength of the best five-card hand, a stronger hand always compares greater.
+/// Bits 20 and up hold the `HandType`, below that the ranks deciding ties four bits each, most significant first.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct HandStrength(u32);
+
+impl HandStrength {
+    fn new(hand_type: HandType, ranks: u32) -> Self {
+        Self(((hand_type as u32) << 20) | ranks)
     }
 
-    if let Some((four, kicker)) = find_four_of_a_kind(&rank_counts, &ranks) {
-        return FullHand{
-            hand_type: HandType::FourOfAKind,
-            ranks: vec![four, kicker]
-        }
+    pub fn hand_type(self) -> HandType {
+        HAND_TYPES[(self.0 >> 20) as usize]
     }
 
-    if let Some((three, pair)) = find_full_house(&rank_counts) {
-        return FullHand {
-            hand_type: HandType::FullHouse,
-            ranks: vec![three, pair]
+    /// Ranks deciding ties between hands of the same type, most significant first
+    pub fn ranks(self) -> Vec<Rank> {
+        let count = match self.hand_type() {
+            HandType::StraightFlush | HandType::Straight => 1,
+            HandType::FourOfAKind | HandType::FullHouse => 2,
+            HandType::ThreeOfAKind | HandType::TwoPair => 3,
+            HandType::OnePair => 4,
+            HandType::Flush | HandType::HighCard => 5,
         };
+        (0..count).map(|i| RANKS[((self.0 >> (16 - 4 * i)) & 0xF) as usize]).collect()
     }
 
-    if let Some(flush_ranks) = find_flush(&color_counts) {
-        return FullHand {
-            hand_type: HandType::Flush,
-            ranks: flush_ranks
-        };
+    pub fn to_full_hand(self) -> FullHand {
+        FullHand { hand_type: self.hand_type(), ranks: self.ranks() }
     }
+}
 
-    if let Some(high) = find_straight(&ranks) {
-        return FullHand {
-            hand_type: HandType::Straight,
-            ranks: vec![high]
-        };
+fn highest_rank(mask: u32) -> u32 {
+    31 - mask.leading_zeros()
+}
+
+/// Evaluates the best five-card hand out of 5 to 7 cards without allocating.
+/// Ranks are kept as 13-bit masks, one per color and one per number of copies,
+/// so every hand type comes down to a few bit operations and table lookups.
+pub fn evaluate(cards: &[Card]) -> HandStrength {
+    let mut colors = [0u32; 4];
+    let mut color_counts = [0u8; 4];
+    for card in cards {
+        colors[card.color as usize] |= 1 << card.rank as u32;
+        color_counts[card.color as usize] += 1;
     }
+    let [hearts, diamonds, clubs, spades] = colors;
 
-    if let Some((three, mut kickers)) = find_three_of_a_kind(&rank_counts, &ranks) {
-        let mut hand_ranks = vec![three];
-        hand_ranks.append(&mut kickers);
-        return FullHand {
-            hand_type: HandType::ThreeOfAKind,
-            ranks: hand_ranks
-        };
+    let flush = color_counts.iter().position(|&count| count >= 5).map(|color| colors[color]);
+    if let Some(flush) = flush {
+        let straight_high = STRAIGHT_HIGHS[flush as usize] as u32;
+        if straight_high != 0 {
+            return HandStrength::new(HandType::StraightFlush, (straight_high - 1) << 16);
+        }
     }
 
-    if let Some((high_pair, low_pair, kicker)) = find_two_pair(&rank_counts,  &ranks) {
-        return FullHand {
-            hand_type: HandType::TwoPair,
-            ranks: vec![high_pair, low_pair, kicker]
-        };
+    let all_ranks = hearts | diamonds | clubs | spades;
+    let two_or_more = (hearts & diamonds) | (clubs & spades) | ((hearts | diamonds) & (clubs | spades));
+    let three_or_more = (hearts & diamonds & (clubs | spades)) | (clubs & spades & (hearts | diamonds));
+    let four = hearts & diamonds & clubs & spades;
+
+    if four != 0 {
+        let quads = highest_rank(four);
+        let kicker = (HIGHEST_RANKS[(all_ranks & !(1 << quads)) as usize] >> 4) & 0xF000;
+        return HandStrength::new(HandType::FourOfAKind, quads << 16 | kicker);
     }
 
-    if let Some((pair, mut kickers)) = find_one_pair(&rank_counts, &ranks) {
-        let mut hand_ranks = vec![pair];
-        hand_ranks.append(&mut kickers);
-        return FullHand{
-            hand_type: HandType::OnePair,
-            ranks: hand_ranks,
-        };
+    if three_or_more != 0 {
+        let trips = highest_rank(three_or_more);
+        // A second set of trips plays as the pair
+        let pairs = two_or_more & !(1 << trips);
+        if pairs != 0 {
+            return HandStrength::new(HandType::FullHouse, trips << 16 | highest_rank(pairs) << 12);
+        }
+    }
+
+    if let Some(flush) = flush {
+        return HandStrength::new(HandType::Flush, HIGHEST_RANKS[flush as usize]);
+    }
+
+    let straight_high = STRAIGHT_HIGHS[all_ranks as usize] as u32;
+    if straight_high != 0 {
+        return HandStrength::new(HandType::Straight, (straight_high - 1) << 16);
+    }
+
+    if three_or_more != 0 {
+        let kickers = (HIGHEST_RANKS[(all_ranks & !three_or_more) as usize] >> 4) & 0xFF00;
+        return HandStrength::new(HandType::ThreeOfAKind, highest_rank(three_or_more) << 16 | kickers);
     }
 
-    // High card
-    FullHand {
-        hand_type: HandType::HighCard,
-        ranks: ranks.iter().take(5).cloned().collect()
+    if two_or_more != 0 {
+        let pair = highest_rank(two_or_more);
+        let other_pairs = two_or_more & !(1 << pair);
+        if other_pairs != 0 {
+            let second_pair = highest_rank(other_pairs);
+            let kicker = (HIGHEST_RANKS[(all_ranks & !(1 << pair) & !(1 << second_pair)) as usize] >> 8) & 0xF00;
+            return HandStrength::new(HandType::TwoPair, pair << 16 | second_pair << 12 | kicker);
+        }
+
+        let kickers = (HIGHEST_RANKS[(all_ranks & !(1 << pair)) as usize] >> 4) & 0xFFF0;
+        return HandStrength::new(HandType::OnePair, pair << 16 | kickers);
     }
+
+    HandStrength::new(HandType::HighCard, HIGHEST_RANKS[all_ranks as usize])
 }
 
-fn find_straight_flush(color_count: &HashMap<&Color, Vec<&Rank>>) -> Option<Rank> {
-    for (_, ranks) in color_count {
-        if ranks.len() >= 5 {
-            let mut sorted = ranks.iter().map(|r| (*r).clone()).collect::<Vec<_>>();
-            sorted.sort();
-            sorted.reverse();
-            if let Some(high) = find_straight(&sorted) {
-                return Some(high);
+#[cfg(test)]
+mod tests {
+    use std::{collections::{BTreeMap, HashSet}, time::Instant};
+
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use super::*;
+
+    /// The evaluator `get_best_hand` used before the bitmask one, kept to check it against.
+    /// Its straight and full house finders are fixed, they panicked or picked the wrong ranks on some hands.
+    mod reference {
+        use std::collections::HashMap;
+
+        use super::super::*;
+
+        pub fn get_best_hand(cards: &[Card]) -> FullHand {
+            let mut color_counts = HashMap::new();
+            let mut rank_counts = HashMap::new();
+
+            let mut ranks: Vec<Rank> = cards.iter().map(|c| c.rank).collect();
+            ranks.sort();
+            ranks.reverse();
+
+            for card in cards {
+                *rank_counts.entry(&card.rank).or_insert(0) += 1_usize;
+                color_counts.entry(&card.color).or_insert(Vec::new()).push(&card.rank);
+            }
+
+            if let Some(high) = find_straight_flush(&color_counts) {
+                return FullHand { hand_type: HandType::StraightFlush, ranks: vec![high] };
+            }
+            if let Some((four, kicker)) = find_four_of_a_kind(&rank_counts, &ranks) {
+                return FullHand { hand_type: HandType::FourOfAKind, ranks: vec![four, kicker] };
+            }
+            if let Some((three, pair)) = find_full_house(&rank_counts) {
+                return FullHand { hand_type: HandType::FullHouse, ranks: vec![three, pair] };
+            }
+            if let Some(flush_ranks) = find_flush(&color_counts) {
+                return FullHand { hand_type: HandType::Flush, ranks: flush_ranks };
+            }
+            if let Some(high) = find_straight(&ranks) {
+                return FullHand { hand_type: HandType::Straight, ranks: vec![high] };
+            }
+            if let Some((three, mut kickers)) = find_three_of_a_kind(&rank_counts, &ranks) {
+                let mut hand_ranks = vec![three];
+                hand_ranks.append(&mut kickers);
+                return FullHand { hand_type: HandType::ThreeOfAKind, ranks: hand_ranks };
+            }
+            if let Some((high_pair, low_pair, kicker)) = find_two_pair(&rank_counts, &ranks) {
+                return FullHand { hand_type: HandType::TwoPair, ranks: vec![high_pair, low_pair, kicker] };
+            }
+            if let Some((pair, mut kickers)) = find_one_pair(&rank_counts, &ranks) {
+                let mut hand_ranks = vec![pair];
+                hand_ranks.append(&mut kickers);
+                return FullHand { hand_type: HandType::OnePair, ranks: hand_ranks };
+            }
+
+            FullHand { hand_type: HandType::HighCard, ranks: ranks.iter().take(5).cloned().collect() }
+        }
+
+        fn find_straight_flush(color_count: &HashMap<&Color, Vec<&Rank>>) -> Option<Rank> {
+            for ranks in color_count.values() {
+                if ranks.len() >= 5 {
+                    let mut sorted = ranks.iter().map(|r| **r).collect::<Vec<_>>();
+                    sorted.sort();
+                    sorted.reverse();
+                    if let Some(high) = find_straight(&sorted) {
+                        return Some(high);
+                    }
+                }
+            }
+
+            None
+        }
+
+        fn find_four_of_a_kind(rank_counts: &HashMap<&Rank, usize>, ranks: &[Rank]) -> Option<(Rank, Rank)> {
+            for (&rank, &count) in rank_counts {
+                if count == 4 {
+                    let kicker = *ranks.iter().find(|&r| r != rank).unwrap();
+                    return Some((*rank, kicker));
+                }
+            }
+            None
+        }
+
+        fn find_full_house(rank_counts: &HashMap<&Rank, usize>) -> Option<(Rank, Rank)> {
+            let three = rank_counts.iter().filter(|(_, &count)| count >= 3).map(|(&&rank, _)| rank).max()?;
+            let pair = rank_counts.iter().filter(|(&&rank, &count)| count >= 2 && rank != three).map(|(&&rank, _)| rank).max()?;
+
+            Some((three, pair))
+        }
+
+        fn find_flush(color_counts: &HashMap<&Color, Vec<&Rank>>) -> Option<Vec<Rank>> {
+            for ranks in color_counts.values() {
+                if ranks.len() >= 5 {
+                    let mut sorted = ranks.iter().map(|r| **r).collect::<Vec<_>>();
+                    sorted.sort();
+                    sorted.reverse();
+                    return Some(sorted.into_iter().take(5).collect());
+                }
+            }
+            None
+        }
+
+        fn find_straight(ranks: &[Rank]) -> Option<Rank> {
+            let mut unique = ranks.to_vec();
+            unique.dedup();
+
+            for window in unique.windows(5) {
+                if window[0] as usize == window[4] as usize + 4 {
+                    return Some(window[0]);
+                }
+            }
+
+            let wheel = [Rank::Ace, Rank::Two, Rank::Three, Rank::Four, Rank::Five];
+            if wheel.iter().all(|rank| unique.contains(rank)) {
+                return Some(Rank::Five);
+            }
+
+            None
+        }
+
+        fn find_three_of_a_kind(rank_counts: &HashMap<&Rank, usize>, ranks: &[Rank]) -> Option<(Rank, Vec<Rank>)> {
+            for (&rank, &count) in rank_counts {
+                if count == 3 {
+                    let kickers = ranks.iter().filter(|&r| r != rank).cloned().take(2).collect();
+                    return Some((*rank, kickers));
+                }
+            }
+            None
+        }
+
+        fn find_two_pair(rank_counts: &HashMap<&Rank, usize>, ranks: &[Rank]) -> Option<(Rank, Rank, Rank)> {
+            let mut pairs = rank_counts.iter()
+                .filter(|(_, &c)| c == 2)
+                .map(|(r, _)| **r)
+                .collect::<Vec<_>>();
+            pairs.sort();
+            pairs.reverse();
+
+            if pairs.len() >= 2 {
+                let kicker = *ranks.iter()
+                    .find(|&r| *r != pairs[0] && *r != pairs[1])
+                    .unwrap();
+                return Some((pairs[0], pairs[1], kicker));
+            }
+            None
+        }
+
+        fn find_one_pair(rank_counts: &HashMap<&Rank, usize>, ranks: &[Rank]) -> Option<(Rank, Vec<Rank>)> {
+            for (&rank, &count) in rank_counts {
+                if count == 2 {
+                    let kickers = ranks.iter().filter(|&r| r != rank).cloned().take(3).collect();
+                    return Some((*rank, kickers));
+                }
             }
+            None
         }
     }
 
-    None
-}
+    fn random_hands(seed: u64, size: usize, count: usize) -> Vec<Vec<Card>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut deck = get_new_deck();
+        (0..count).map(|_| {
+            deck.shuffle(&mut rng);
+            deck[..size].to_vec()
+        }).collect()
+    }
 
-fn find_four_of_a_kind(rank_counts: &HashMap<&Rank, usize>, ranks: &[Rank]) -> Option<(Rank, Rank)> {
-    for (&rank, &count) in rank_counts {
-        if count == 4 {
-            let kicker = ranks.iter().filter(|&r| r != rank).next().unwrap().clone();
-            return Some((rank.clone(), kicker));
+    #[test]
+    fn matches_reference_evaluator() {
+        for size in 5..=7 {
+            let mut hand_types = BTreeMap::new();
+            for cards in random_hands(size as u64, size, 50_000) {
+                let fast = evaluate(&cards).to_full_hand();
+                let reference = reference::get_best_hand(&cards);
+                assert_eq!((fast.hand_type, &fast.ranks), (reference.hand_type, &reference.ranks), "{:?}", cards);
+                *hand_types.entry(fast.hand_type).or_insert(0) += 1;
+            }
+            assert_eq!(hand_types.len(), 9, "every hand type should come up with {} cards", size);
         }
     }
-    None
-}
 
-fn find_full_house(rank_counts: &HashMap<&Rank, usize>) -> Option<(Rank, Rank)> {
-    let mut three = None;
-    let mut pair = None;
+    #[test]
+    fn strength_orders_like_compare_hands() {
+        let hands = random_hands(0, 7, 2_000);
+        for pair in hands.windows(2) {
+            let (first, second) = (evaluate(&pair[0]), evaluate(&pair[1]));
+            assert_eq!(first.cmp(&second), compare_hands(&first.to_full_hand(), &second.to_full_hand()));
+        }
+    }
 
-    for (&rank, &count) in rank_counts {
-        if count >= 3 {
-            if three.is_none() { three = Some(rank.clone()); }
-        } else if count >= 2 {
-            if pair.is_none() { pair = Some(rank.clone()); }
+    /// Run with `cargo test --release -- --ignored evaluation_speed --nocapture`
+    #[test]
+    #[ignore]
+    fn evaluation_speed() {
+        let hands: Vec<[Card; 7]> = random_hands(0, 7, 1_000_000).iter().map(|cards| cards[..].try_into().unwrap()).collect();
+        let started = Instant::now();
+        let mut total = 0u64;
+        for _ in 0..10 {
+            for cards in &hands {
+                total = total.wrapping_add(evaluate(cards).0 as u64);
+            }
         }
+        let elapsed = started.elapsed();
+        println!("{:.1} million evaluations per second ({})", 10.0 / elapsed.as_secs_f64(), total);
     }
 
-    if three.is_some() && pair.is_some() {
-        return Some((three.unwrap(), pair.unwrap()));
+    fn cards<const N: usize>(text: &str) -> [Card; N] {
+        parse_cards(text).unwrap().try_into().unwrap()
     }
 
-    None
-}
+    fn count_hand_types(size: usize) -> (BTreeMap<HandType, u64>, usize) {
+        let mut counts = BTreeMap::new();
+        let mut strengths = HashSet::new();
+        for_each_combination(&get_new_deck(), size, |hand| {
+            let strength = evaluate(hand);
+            *counts.entry(strength.hand_type()).or_insert(0) += 1;
+            strengths.insert(strength);
+        });
+
+        (counts, strengths.len())
+    }
+
+    #[test]
+    fn five_card_hand_type_distribution() {
+        let (counts, distinct) = count_hand_types(5);
+
+        assert_eq!(counts, BTreeMap::from([
+            (HandType::StraightFlush, 40),
+            (HandType::FourOfAKind, 624),
+            (HandType::FullHouse, 3_744),
+            (HandType::Flush, 5_108),
+            (HandType::Straight, 10_200),
+            (HandType::ThreeOfAKind, 54_912),
+            (HandType::TwoPair, 123_552),
+            (HandType::OnePair, 1_098_240),
+            (HandType::HighCard, 1_302_540),
+        ]));
+        assert_eq!(counts.values().sum::<u64>(), 2_598_960);
+        // Hands only differing by colors are worth the same, which leaves 7462 distinct strengths
+        assert_eq!(distinct, 7_462);
+    }
+
+    /// Takes a few seconds in release, run with `cargo test --release -- --ignored seven_card`
+    #[test]
+    #[ignore]
+    fn seven_card_hand_type_distribution() {
+        let (counts, distinct) = count_hand_types(7);
+
+        assert_eq!(counts, BTreeMap::from([
+            (HandType::StraightFlush, 41_584),
+            (HandType::FourOfAKind, 224_848),
+            (HandType::FullHouse, 3_473_184),
+            (HandType::Flush, 4_047_644),
+            (HandType::Straight, 6_180_020),
+            (HandType::ThreeOfAKind, 6_461_620),
+            (HandType::TwoPair, 31_433_400),
+            (HandType::OnePair, 58_627_800),
+            (HandType::HighCard, 23_294_460),
+        ]));
+        // Seven-card hands can't end up with the weakest high cards, 4824 of the 7462 strengths remain
+        assert_eq!(distinct, 4_824);
+    }
+
+    fn best_of_five(cards: &[Card]) -> HandStrength {
+        let mut best = None;
+        for_each_combination(cards, 5, |hand| {
+            best = best.max(Some(evaluate(hand)));
+        });
+
+        best.unwrap()
+    }
 
-fn find_flush(color_counts: &HashMap<&Color, Vec<&Rank>>) -> Option<Vec<Rank>> {
-    for (_, ranks) in color_counts {
-        if ranks.len() >= 5 {
-            let mut sorted = ranks.iter().map(|r| (*r).clone()).collect::<Vec<_>>();
-            sorted.sort();
-            sorted.reverse();
-            return Some(sorted.into_iter().take(5).collect());
+    #[test]
+    fn seven_cards_play_the_best_five() {
+        for cards in random_hands(1, 7, 20_000) {
+            assert_eq!(evaluate(&cards), best_of_five(&cards), "{:?}", cards);
         }
     }
-    None
-}
 
-fn find_straight(ranks: &[Rank]) -> Option<Rank> {
-    let mut unique = ranks.to_vec();
-    unique.dedup();
-
-    for i in 0..(unique.len() - 5).max(0) {
-        let values: Vec<u8> = unique[i..i + 5].iter().map(|r| match r {
-            Rank::Two => 2,
-            Rank::Three => 3,
-            Rank::Four => 4,
-            Rank::Five => 5,
-            Rank::Six => 6,
-            Rank::Seven => 7,
-            Rank::Eight => 8,
-            Rank::Nine => 9,
-            Rank::Ten => 10,
-            Rank::Jack => 11,
-            Rank::Queen => 12,
-            Rank::King => 13,
-            Rank::Ace => 14,
-        }).collect();
-    
-        let consecutive = values.windows(2).all(|w| w[0] == w[1] + 1);
-
-        if consecutive {
-            return Some(unique[i].clone())
-        }
-
-        if unique.contains(&Rank::Ace)
-        && unique.contains(&Rank::Two)
-        && unique.contains(&Rank::Three)
-        && unique.contains(&Rank::Four)
-        && unique.contains(&Rank::Five) {
-            return Some(Rank::Five);
-        }
-    }
-
-    None
-}
+    #[test]
+    fn strength_ignores_card_order_and_color_names() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let colors = [Color::Heart, Color::Diamond, Color::Club, Color::Spade];
+        for mut cards in random_hands(3, 7, 20_000) {
+            let strength = evaluate(&cards);
 
-fn find_three_of_a_kind(rank_counts: &HashMap<&Rank, usize>, ranks: &[Rank]) -> Option<(Rank, Vec<Rank>)> {
-    for (&rank, &count) in rank_counts {
-        if count == 3 {
-            let kickers = ranks.iter().filter(|&r| r != rank).cloned().take(2).collect();
-            return Some((rank.clone(), kickers));
+            cards.shuffle(&mut rng);
+            assert_eq!(evaluate(&cards), strength);
+
+            let mut renamed = colors;
+            renamed.shuffle(&mut rng);
+            let relabeled: Vec<_> = cards.iter().map(|card| Card { color: renamed[card.color as usize], rank: card.rank }).collect();
+            assert_eq!(evaluate(&relabeled), strength);
         }
     }
-    None
-}
 
-fn find_two_pair(rank_counts: &HashMap<&Rank, usize>, ranks: &[Rank]) -> Option<(Rank, Rank, Rank)> {
-    let mut pairs = rank_counts.iter()
-        .filter(|(_, &c)| c == 2)
-        .map(|(r, _)| (*r).clone())
-        .collect::<Vec<_>>();
-    pairs.sort();
-    pairs.reverse();
-
-    if pairs.len() >= 2 {
-        let kicker = ranks.iter()
-            .filter(|&r| *r != pairs[0] && *r != pairs[1])
-            .next()
-            .unwrap()
-            .clone();
-        return Some((pairs[0].clone(), pairs[1].clone(), kicker));
-    }
-    None
-}
+    #[test]
+    fn compare_hands_is_a_total_order_matching_strength() {
+        let hands: Vec<_> = random_hands(4, 7, 500).into_iter().map(|cards| (evaluate(&cards), get_best_hand(cards.try_into().unwrap()))).collect();
+        for (strength1, hand1) in &hands {
+            for (strength2, hand2) in &hands {
+                let ordering = compare_hands(hand1, hand2);
+                assert_eq!(ordering, strength1.cmp(strength2));
+                assert_eq!(ordering.reverse(), compare_hands(hand2, hand1));
+                if hand1.hand_type != hand2.hand_type {
+                    assert_eq!(ordering, hand1.hand_type.cmp(&hand2.hand_type));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hand_type_beats_higher_ranks() {
+        let pair = get_best_hand(cards("2h 2d 5c 7s 9h Jd 4c"));
+        let high_card = get_best_hand(cards("Ah Kd Qc 9s 7h 5d 3c"));
+        assert_eq!(pair.hand_type, HandType::OnePair);
+        assert_eq!(compare_hands(&pair, &high_card), Ordering::Greater);
+    }
+
+    #[test]
+    fn finds_straights_at_every_position() {
+        // Only five distinct ranks, the straight is the whole hand
+        let hand = get_best_hand(cards("5h 6d 7c 8s 9h 9d 5c"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::Straight, vec![Rank::Nine]));
 
-fn find_one_pair(rank_counts: &HashMap<&Rank, usize>, ranks: &[Rank]) -> Option<(Rank, Vec<Rank>)> {
-    for (&rank, &count) in rank_counts {
-        if count == 2 {
-            let kickers = ranks.iter().filter(|&r| r != rank).cloned().take(3).collect();
-            return Some((rank.clone(), kickers));
+        // The straight is made of the five lowest distinct ranks
+        let hand = get_best_hand(cards("2h 3d 4c 5s 6h Jd Kc"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::Straight, vec![Rank::Six]));
+
+        let hand = get_best_hand(cards("Ah 2d 3c 4s 5h 9d Kc"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::Straight, vec![Rank::Five]));
+
+        let hand = get_best_hand(cards("Th Jd Qc Ks Ah 2d 3c"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::Straight, vec![Rank::Ace]));
+
+        // Ace to Six is the higher of the two straights
+        let hand = get_best_hand(cards("Ah 2d 3c 4s 5h 6d Kc"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::Straight, vec![Rank::Six]));
+    }
+
+    #[test]
+    fn fewer_than_five_distinct_ranks() {
+        let hand = get_best_hand(cards("2h 2d 3c 3s 4h 4d 5c"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::TwoPair, vec![Rank::Four, Rank::Three, Rank::Five]));
+
+        let hand = get_best_hand(cards("Kh Kd Kc 7s 7h 7d 2c"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::FullHouse, vec![Rank::King, Rank::Seven]));
+    }
+
+    #[test]
+    fn full_house_uses_the_highest_trips() {
+        let hand = get_best_hand(cards("3h 3d 3c Qs Qh Qd 9c"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::FullHouse, vec![Rank::Queen, Rank::Three]));
+
+        let hand = get_best_hand(cards("8h 8d 8c 4s 4h Jd Jc"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::FullHouse, vec![Rank::Eight, Rank::Jack]));
+    }
+
+    #[test]
+    fn straight_flush_needs_one_color() {
+        // A heart flush and a straight through other colors is only a flush
+        let hand = get_best_hand(cards("2h 5h 6h 9h Kh 7d 8c"));
+        assert_eq!((hand.hand_type, hand.ranks[0]), (HandType::Flush, Rank::King));
+
+        let hand = get_best_hand(cards("5s 6s 7s 8s 9s Ts 4d"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::StraightFlush, vec![Rank::Ten]));
+
+        let hand = get_best_hand(cards("As 2s 3s 4s 5s Kd Qd"));
+        assert_eq!((hand.hand_type, hand.ranks), (HandType::StraightFlush, vec![Rank::Five]));
+    }
+
+    #[test]
+    fn kickers_break_ties() {
+        let hand1 = get_best_hand(cards("Ah Ad Kc 9s 7h 4d 2c"));
+        let hand2 = get_best_hand(cards("As Ac Qc Js 7h 4d 2c"));
+        assert_eq!(compare_hands(&hand1, &hand2), Ordering::Greater);
+
+        // The sixth and seventh cards don't play
+        let hand1 = get_best_hand(cards("Ah Kd Qc Js 9h 4d 3c"));
+        let hand2 = get_best_hand(cards("As Kc Qd Jh 9s 2d 3h"));
+        assert_eq!(compare_hands(&hand1, &hand2), Ordering::Equal);
+    }
+
+    #[test]
+    fn cards_round_trip_through_notation() {
+        for card in get_new_deck() {
+            assert_eq!(card.to_string().parse::<Card>(), Ok(card));
         }
+        assert_eq!("td".parse(), Ok(Card { rank: Rank::Ten, color: Color::Diamond }));
+        assert_eq!("10d".parse::<Card>(), Err(ParseCardError::InvalidLength));
+        assert_eq!("Xh".parse::<Card>(), Err(ParseCardError::InvalidRank));
+        assert_eq!("Ax".parse::<Card>(), Err(ParseCardError::InvalidColor));
+
+        let cards = parse_cards("AhKd Qs\t2c").unwrap();
+        assert_eq!(cards.iter().map(Card::to_string).collect::<Vec<_>>(), ["Ah", "Kd", "Qs", "2c"]);
+        assert_eq!(parse_cards("AhK"), Err(ParseCardError::InvalidLength));
+        assert_eq!(parse_cards(""), Ok(Vec::new()));
     }
-    None
-}
\ No newline at end of file
+
+    #[test]
+    fn compact_serde_uses_notation() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Fixture {
+            #[serde(with = "compact")]
+            card: Card,
+            #[serde(with = "compact::list")]
+            board: Vec<Card>,
+            verbose: Card,
+        }
+
+        let fixture = Fixture { card: "Ah".parse().unwrap(), board: parse_cards("Td 9c 2s").unwrap(), verbose: "2c".parse().unwrap() };
+        let json = serde_json::to_string(&fixture).unwrap();
+        assert_eq!(json, r#"{"card":"Ah","board":"Td 9c 2s","verbose":{"color":"Club","rank":"Two"}}"#);
+        assert_eq!(serde_json::from_str::<Fixture>(&json).unwrap(), fixture);
+        assert!(serde_json::from_str::<Fixture>(r#"{"card":"Ahh","board":"","verbose":{"color":"Club","rank":"Two"}}"#).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+//! Chances of each player to win once all the cards are dealt.
+//! Every remaining board is enumerated when there are at most `samples` of them,
+//! otherwise `samples` boards are drawn at random.
+
+use std::fmt;
+
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
+
+use super::card::{evaluate, for_each_combination, get_new_deck, Card, HandStrength};
+
+/// Percentages of the boards a player wins alone, splits with others and loses
+#[derive(Serialize, Copy, Clone, PartialEq, Debug, Default)]
+pub struct Equity {
+    pub win: f64,
+    pub tie: f64,
+    pub loss: f64,
+}
+
+#[derive(Serialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EquityError {
+    NotEnoughPlayers,
+    TooManyBoardCards,
+    DuplicateCard,
+    NotEnoughCards,
+    NoSamples,
+}
+
+impl fmt::Display for EquityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            EquityError::NotEnoughPlayers => "At least two hands are needed",
+            EquityError::TooManyBoardCards => "The board has at most five cards",
+            EquityError::DuplicateCard => "A card is used more than once",
+            EquityError::NotEnoughCards => "Not enough cards left to complete the board",
+            EquityError::NoSamples => "At least one board must be looked at",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for EquityError {}
+
+fn card_index(card: Card) -> usize {
+    card.color as usize * 13 + card.rank as usize
+}
+
+/// Number of ways to pick `k` cards out of `n`, saturating
+fn combinations(n: usize, k: usize) -> u64 {
+    (0..k as u64).fold(1u64, |total, i| total.saturating_mul(n as u64 - i) / (i + 1))
+}
+
+/// Equity of each of `hands` with the given `board` (0 to 5 cards), `dead` cards being out of the deck
+pub fn calculate(hands: &[[Card; 2]], board: &[Card], dead: &[Card], samples: usize, rng: &mut impl Rng) -> Result<Vec<Equity>, EquityError> {
+    if hands.len() < 2 {
+        return Err(EquityError::NotEnoughPlayers);
+    }
+    if board.len() > 5 {
+        return Err(EquityError::TooManyBoardCards);
+    }
+    if samples == 0 {
+        return Err(EquityError::NoSamples);
+    }
+
+    let mut used = [false; 52];
+    for &card in hands.iter().flatten().chain(board).chain(dead) {
+        if std::mem::replace(&mut used[card_index(card)], true) {
+            return Err(EquityError::DuplicateCard);
+        }
+    }
+    let deck: Vec<Card> = get_new_deck().into_iter().filter(|&card| !used[card_index(card)]).collect();
+    let missing = 5 - board.len();
+    if deck.len() < missing {
+        return Err(EquityError::NotEnoughCards);
+    }
+
+    let mut tally = Tally::new(hands, board);
+    if combinations(deck.len(), missing) <= samples as u64 {
+        for_each_combination(&deck, missing, |runout| tally.add(runout.iter().copied()));
+    } else {
+        let mut deck = deck;
+        for _ in 0..samples {
+            let (drawn, _) = deck.partial_shuffle(rng, missing);
+            tally.add(drawn.iter().copied());
+        }
+    }
+
+    Ok(tally.equities())
+}
+
+/// Counts of boards won, split and lost by each hand
+struct Tally {
+    /// Hole cards followed by the board, the unknown board cards get overwritten for each board
+    cards: Vec<[Card; 7]>,
+    known: usize,
+    strengths: Vec<HandStrength>,
+    wins: Vec<u64>,
+    ties: Vec<u64>,
+    boards: u64,
+}
+
+impl Tally {
+    fn new(hands: &[[Card; 2]], board: &[Card]) -> Self {
+        let cards: Vec<[Card; 7]> = hands.iter().map(|hand| {
+            let mut cards = [hand[0]; 7];
+            cards[1] = hand[1];
+            cards[2..2 + board.len()].copy_from_slice(board);
+            cards
+        }).collect();
+
+        Self {
+            strengths: cards.iter().map(|cards| evaluate(cards)).collect(),
+            cards,
+            known: 2 + board.len(),
+            wins: vec![0; hands.len()],
+            ties: vec![0; hands.len()],
+            boards: 0,
+        }
+    }
+
+    fn add(&mut self, runout: impl Iterator<Item = Card> + Clone) {
+        for (cards, strength) in self.cards.iter_mut().zip(self.strengths.iter_mut()) {
+            for (slot, card) in cards[self.known..].iter_mut().zip(runout.clone()) {
+                *slot = card;
+            }
+            *strength = evaluate(cards);
+        }
+
+        let best = self.strengths.iter().max().copied();
+        let mut winners = self.strengths.iter().enumerate().filter(|&(_, &strength)| Some(strength) == best).map(|(i, _)| i);
+        match (winners.next(), winners.next()) {
+            (Some(winner), None) => self.wins[winner] += 1,
+            (Some(first), Some(second)) => {
+                for i in [first, second].into_iter().chain(winners) {
+                    self.ties[i] += 1;
+                }
+            }
+            _ => {}
+        }
+        self.boards += 1;
+    }
+
+    fn equities(&self) -> Vec<Equity> {
+        let boards = self.boards.max(1) as f64;
+        self.wins.iter().zip(&self.ties).map(|(&wins, &ties)| {
+            let win = wins as f64 * 100.0 / boards;
+            let tie = ties as f64 * 100.0 / boards;
+            Equity { win, tie, loss: 100.0 - win - tie }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::engine::card::parse_cards;
+
+    #[test]
+    fn river_draw_is_enumerated() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let flush_draw = ["9h".parse().unwrap(), "8h".parse().unwrap()];
+        let aces = ["As".parse().unwrap(), "Ad".parse().unwrap()];
+        let board = parse_cards("2h 5h Kc Jd").unwrap();
+
+        let equities = calculate(&[flush_draw, aces], &board, &[], 1000, &mut rng).unwrap();
+        // 9 hearts among the 44 unseen cards
+        assert!((equities[0].win - 900.0 / 44.0).abs() < 1e-9);
+        assert!((equities[1].win - 3500.0 / 44.0).abs() < 1e-9);
+        assert_eq!(equities[0].tie, 0.0);
+
+        // Knowing two hearts are gone leaves 7 outs among 42 cards
+        let dead = parse_cards("3h4h").unwrap();
+        let equities = calculate(&[flush_draw, aces], &board, &dead, 1000, &mut rng).unwrap();
+        assert!((equities[0].win - 700.0 / 42.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn samples_come_close_to_enumeration() {
+        let aces = ["Ah".parse().unwrap(), "As".parse().unwrap()];
+        let kings = ["Kd".parse().unwrap(), "Kc".parse().unwrap()];
+        let exact = calculate(&[aces, kings], &[], &[], 2_000_000, &mut StdRng::seed_from_u64(0)).unwrap();
+        let sampled = calculate(&[aces, kings], &[], &[], 20_000, &mut StdRng::seed_from_u64(1)).unwrap();
+
+        assert!(exact[0].win > 80.0 && exact[0].win < 83.0);
+        for (exact, sampled) in exact.iter().zip(&sampled) {
+            assert!((exact.win - sampled.win).abs() < 1.5);
+            assert!((exact.tie - sampled.tie).abs() < 1.5);
+            assert!((exact.win + exact.tie + exact.loss - 100.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let aces = ["Ah".parse().unwrap(), "As".parse().unwrap()];
+        let kings = ["Kd".parse().unwrap(), "Kc".parse().unwrap()];
+
+        assert_eq!(calculate(&[aces, aces], &[], &[], 100, &mut rng), Err(EquityError::DuplicateCard));
+        assert_eq!(calculate(&[aces, kings], &[aces[0]], &[], 100, &mut rng), Err(EquityError::DuplicateCard));
+        assert_eq!(calculate(&[aces], &[], &[], 100, &mut rng), Err(EquityError::NotEnoughPlayers));
+        assert_eq!(calculate(&[aces, kings], &[], &[], 0, &mut rng), Err(EquityError::NoSamples));
+    }
+}
@@ -0,0 +1,149 @@
+//! Provably fair dealing.
+//!
+//! Before a hand the table publishes `commitment(server_seed)`, the SHA-256 of a secret server seed.
+//! The deck is then derived from the server seed and the players' client seeds, and the server seed
+//! is revealed in the hand history once the hand is over, so anyone can redo the shuffle:
+//!
+//! 1. `key = SHA-256(len(server_seed) || server_seed || seat_1 || len(client_seed_1) || client_seed_1 || ...)`,
+//!    client seeds in seat order, seats and byte lengths as u64 big endian so no two seed lists hash alike
+//! 2. the `n`-th random number is the first 8 bytes, big endian, of `SHA-256(key || n as u64 big endian)`
+//! 3. starting from `get_new_deck()`, for `i` from 51 down to 1 swap card `i` with card `j`, `j` uniform
+//!    in `0..=i`, drawn by rejecting the numbers that would bias `number % (i + 1)`
+//!
+//! The board is dealt from the top five cards, then each seat gets the two cards after that,
+//! seat `i` taking cards `5 + 2i` and `6 + 2i`.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{card::{get_new_deck, Card}, history::HandHistory};
+
+/// What is needed to redo the shuffle of a provably fair hand
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FairnessRecord {
+    pub server_seed: String,
+    pub commitment: String,
+    pub client_seeds: Vec<ClientSeed>,
+}
+
+/// Seed a player set with `/seed`, along with the seat they played the hand from
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct ClientSeed {
+    pub seat: usize,
+    pub seed: String,
+}
+
+/// Random server seed, 256 bits encoded as hex
+pub fn new_server_seed(rng: &mut impl Rng) -> String {
+    let bytes: [u8; 32] = rng.random();
+    to_hex(&bytes)
+}
+
+/// Hash of the server seed published before the hand
+pub fn commitment(server_seed: &str) -> String {
+    to_hex(&Sha256::digest(server_seed.as_bytes()))
+}
+
+pub fn shuffled_deck(server_seed: &str, client_seeds: &[ClientSeed]) -> Vec<Card> {
+    let mut hasher = Sha256::new();
+    hasher.update((server_seed.len() as u64).to_be_bytes());
+    hasher.update(server_seed.as_bytes());
+    for ClientSeed { seat, seed } in client_seeds {
+        hasher.update((*seat as u64).to_be_bytes());
+        hasher.update((seed.len() as u64).to_be_bytes());
+        hasher.update(seed.as_bytes());
+    }
+    let key = hasher.finalize();
+
+    let mut counter: u64 = 0;
+    let mut next_number = || {
+        let hash = Sha256::new().chain_update(key).chain_update(counter.to_be_bytes()).finalize();
+        counter += 1;
+        u64::from_be_bytes(hash[..8].try_into().unwrap())
+    };
+
+    let mut deck = get_new_deck();
+    for i in (1..deck.len()).rev() {
+        let bound = i as u64 + 1;
+        let limit = u64::MAX - u64::MAX % bound;
+        let j = loop {
+            let number = next_number();
+            if number < limit { break number % bound; }
+        };
+        deck.swap(i, j as usize);
+    }
+
+    deck
+}
+
+/// Checks that the revealed server seed matches its commitment and that the recorded board
+/// and hole cards come out of the shuffle. Hidden hole cards are skipped.
+pub fn verify_hand(hand: &HandHistory) -> bool {
+    let Some(record) = &hand.fairness else { return false; };
+    if commitment(&record.server_seed) != record.commitment { return false; }
+
+    let deck = shuffled_deck(&record.server_seed, &record.client_seeds);
+    if !hand.board.iter().zip(&deck).all(|(card, dealt)| card == dealt) { return false; }
+
+    hand.seats.iter().all(|seat| {
+        let dealt = [deck.get(5 + 2 * seat.seat), deck.get(6 + 2 * seat.seat)];
+        seat.cards.iter().zip(dealt).all(|(card, dealt)| card.is_none() || card.as_ref() == dealt)
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::table::{tests::table_with_players, PlayerAction};
+
+    #[test]
+    fn provably_fair_hand_verifies() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.provably_fair = true;
+        table.players[1].as_mut().unwrap().client_seed = Some("bob's seed".to_string());
+
+        let published = table.server_seed_hash.clone();
+        table.start_new_game().unwrap();
+        assert_ne!(table.server_seed_hash, published);
+        for _ in 0..2 {
+            table.player_action(table.current_player_index, PlayerAction::Fold).unwrap();
+        }
+
+        let mut hand = table.hand_histories.back().unwrap().clone();
+        let record = hand.fairness.clone().unwrap();
+        assert_eq!(record.commitment, published);
+        assert_eq!(record.client_seeds, [ClientSeed { seat: 1, seed: "bob's seed".to_string() }]);
+        hand.board = table.cards.iter().flatten().copied().collect();
+        assert!(verify_hand(&hand));
+
+        hand.fairness.as_mut().unwrap().client_seeds.clear();
+        assert!(!verify_hand(&hand));
+    }
+
+    #[test]
+    fn seed_boundaries_and_seats_change_the_deck() {
+        let seed = |seat: usize, seed: &str| ClientSeed { seat, seed: seed.to_string() };
+        let deck = shuffled_deck("server", &[seed(0, "a:b")]);
+        assert_ne!(deck, shuffled_deck("server", &[seed(0, "a"), seed(1, "b")]));
+        assert_ne!(deck, shuffled_deck("server:a", &[seed(0, "b")]));
+        assert_ne!(deck, shuffled_deck("server", &[seed(1, "a:b")]));
+        assert_eq!(deck, shuffled_deck("server", &[seed(0, "a:b")]));
+    }
+
+    #[test]
+    fn shuffle_is_a_permutation() {
+        let mut deck = shuffled_deck("seed", &[]);
+        assert_eq!(deck.len(), 52);
+        assert_ne!(deck, get_new_deck());
+
+        deck.sort_by_key(|card| (card.color, card.rank));
+        let mut new_deck = get_new_deck();
+        new_deck.sort_by_key(|card| (card.color, card.rank));
+        assert_eq!(deck, new_deck);
+    }
+}
@@ -0,0 +1,133 @@
+use serde::Serialize;
+
+use super::{card::{Card, FullHand}, fairness::FairnessRecord, table::Phase};
+
+#[derive(Serialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HandAction {
+    SmallBlind,
+    BigBlind,
+    Fold,
+    Check,
+    Call,
+    Bet,
+    Raise,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SeatRecord {
+    pub seat: usize,
+    pub name: String,
+    /// Stack before the blinds were posted
+    pub chips: i32,
+    pub cards: [Option<Card>; 2],
+    #[serde(skip)]
+    pub key: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ActionRecord {
+    pub phase: Phase,
+    pub seat: usize,
+    pub action: HandAction,
+    /// Chips put in by this action
+    pub amount: i32,
+    /// Player's total bet on the street after this action
+    pub total_bet: i32,
+    pub is_all_in: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ShowdownRecord {
+    pub seat: usize,
+    pub cards: [Option<Card>; 2],
+    pub hand: FullHand,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Winner {
+    pub seat: usize,
+    pub amount: i32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PotAward {
+    pub amount: i32,
+    pub winners: Vec<Winner>,
+}
+
+/// Everything that happened during one hand, from the deal to the pot awards
+#[derive(Serialize, Clone, Debug)]
+pub struct HandHistory {
+    pub hand_id: u64,
+    pub table_id: u64,
+    pub table_name: String,
+    /// Unix timestamp of the deal
+    pub started_at: i64,
+    /// Seed the deck was shuffled with, see `Table::start_new_game_with_seed`.
+    /// It gives away every mucked hand, so only the server's own copy keeps it
+    pub seed: Option<u64>,
+    /// Seeds of a provably fair hand
+    pub fairness: Option<FairnessRecord>,
+    /// Number of seats at the table, empty ones included
+    pub seat_count: usize,
+    pub button_index: usize,
+    pub small_blind: i32,
+    pub big_blind: i32,
+    pub seats: Vec<SeatRecord>,
+    pub actions: Vec<ActionRecord>,
+    pub board: Vec<Card>,
+    pub showdown: Vec<ShowdownRecord>,
+    pub awards: Vec<PotAward>,
+}
+
+impl HandHistory {
+    /// Copy of the history with the hole cards hidden, except the viewer's own and the ones shown at showdown,
+    /// and without the seed they could be dealt again from
+    pub fn seen_by(&self, key: &str) -> HandHistory {
+        let mut history = self.clone();
+        history.seed = None;
+        for seat in history.seats.iter_mut() {
+            let is_shown = self.showdown.iter().any(|record| record.seat == seat.seat);
+            if seat.key != key && !is_shown {
+                seat.cards = [const { None }; 2];
+            }
+        }
+
+        history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::table::{tests::table_with_players, PlayerAction};
+
+    #[test]
+    fn others_only_see_the_cards_shown_at_showdown() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.start_new_game().unwrap();
+        table.player_action(0, PlayerAction::Call).unwrap();
+        table.player_action(1, PlayerAction::Fold).unwrap();
+        while table.is_game_running() {
+            table.player_action(table.current_player_index, PlayerAction::Check).unwrap();
+        }
+        let hand = table.hand_histories.back().unwrap();
+        assert!(hand.seed.is_some());
+        assert_eq!(hand.showdown.len(), 2);
+
+        let hidden = [const { None }; 2];
+        let seen = hand.seen_by("d");
+        assert_eq!(seen.seed, None);
+        assert_eq!(seen.seats[1].cards, hidden);
+        assert_eq!(seen.seats[0].cards, hand.seats[0].cards);
+        assert_eq!(seen.seats[2].cards, hand.seats[2].cards);
+        assert_eq!(seen.board, hand.board);
+        assert_eq!(seen.actions.len(), hand.actions.len());
+        assert_eq!(seen.awards.len(), hand.awards.len());
+
+        // Bob still sees the cards he folded
+        let seen = hand.seen_by("b");
+        assert_eq!(seen.seed, None);
+        assert_eq!(seen.seats[1].cards, hand.seats[1].cards);
+        assert_ne!(seen.seats[1].cards, hidden);
+    }
+}
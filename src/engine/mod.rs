@@ -1,29 +1,183 @@
-use table::Table;
+use std::{collections::{BTreeMap, HashMap}, time::Instant};
+
+use rand::Rng;
+use player::Player;
+use table::{Table, TableError};
 
 pub mod card;
+pub mod equity;
+pub mod fairness;
+pub mod history;
 pub mod player;
+pub mod pokerstars;
 pub mod table;
 
 #[derive(Clone)]
 pub struct Engine {
-    pub tables: Vec<Table>
+    pub tables: BTreeMap<u64, Table>,
+    next_table_id: u64,
+    /// Player key -> (table id, seat), kept in sync by every method adding or removing players
+    player_index: HashMap<String, (u64, usize)>,
 }
 
 
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Engine {
     pub fn new() -> Self {
         Self {
-            tables: vec![],
+            tables: BTreeMap::new(),
+            next_table_id: 0,
+            player_index: HashMap::new(),
+        }
+    }
+
+    pub fn get_tables(&mut self) -> &mut BTreeMap<u64, Table> { &mut self.tables }
+
+    /// Key and chips of every seated player, counting the chips they put in the current hand
+    pub fn open_stacks(&self) -> Vec<(String, i64)> {
+        self.tables.values()
+            .flat_map(|table| table.players.iter().flatten())
+            .filter(|player| !player.is_leaving)
+            .map(|player| (player.key.clone(), (player.chips + player.total_bet) as i64))
+            .collect()
+    }
+
+    /// Returns the table id and seat of the player holding `key`
+    pub fn find_player(&self, key: &str) -> Option<(u64, usize)> {
+        self.player_index.get(key).copied()
+    }
+
+    pub fn get_player_table(&mut self, key: &str) -> Option<(&mut Table, usize)> {
+        let (id, seat) = self.find_player(key)?;
+        Some((self.tables.get_mut(&id)?, seat))
+    }
+
+    /// Creates a table and returns its id along with the host's key.
+    /// Ids are never reused so they stay valid while other tables come and go.
+    pub fn new_table(&mut self, name: String, table_name: String, max_players: usize, small_blind: i32, big_blind: i32, starting_chips: i32) -> (u64, String) {
+        let id = self.next_table_id;
+        self.next_table_id += 1;
+
+        let key = new_player_key();
+        self.tables.insert(id, Table::new(id, name, table_name, max_players, small_blind, big_blind, starting_chips, key.clone()));
+        self.index_table(id);
+
+        (id, key)
+    }
+
+    pub fn remove_table(&mut self, id: u64) {
+        if let Some(table) = self.tables.remove(&id) {
+            for player in table.players.iter().flatten() {
+                self.player_index.remove(&player.key);
+            }
+        }
+    }
+
+    /// Seats a new player at the table, in `seat` if given, and returns their key
+    pub fn add_player(&mut self, id: u64, name: String, seat: Option<usize>) -> Result<String, TableError> {
+        let table = self.tables.get_mut(&id).ok_or(TableError::TableNotFound)?;
+
+        let key = new_player_key();
+        let seat = table.add_player(name, key.clone(), seat)?;
+        self.player_index.insert(key.clone(), (id, seat));
+
+        Ok(key)
+    }
+
+    /// Removes the player from their table and returns them, the table is closed once the last player leaves
+    pub fn remove_player(&mut self, key: &str) -> Result<Player, TableError> {
+        let (id, seat) = self.find_player(key).ok_or(TableError::SeatEmpty)?;
+        let table = self.tables.get_mut(&id).ok_or(TableError::TableNotFound)?;
+
+        let player = table.remove_player(seat)?;
+        self.player_index.remove(key);
+
+        if table.players.iter().flatten().all(|player| player.is_leaving) {
+            self.remove_table(id);
+        }
+
+        Ok(player)
+    }
+
+    /// Acts for every player whose turn timer ran out and deals the streets of all-in run outs
+    pub fn handle_timeouts(&mut self) {
+        let now = Instant::now();
+        for table in self.tables.values_mut() {
+            table.deal_next_street(now);
+            table.handle_timeout(now);
+        }
+    }
+
+    fn index_table(&mut self, id: u64) {
+        let Some(table) = self.tables.get(&id) else { return; };
+        for (seat, player) in table.players.iter().enumerate() {
+            if let Some(player) = player {
+                self.player_index.insert(player.key.clone(), (id, seat));
+            }
         }
     }
+}
+
+/// Random session token, 256 bits from the thread-local CSPRNG encoded as hex
+fn new_player_key() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
 
-    pub fn get_tables(&mut self) -> &mut Vec<Table> { &mut self.tables }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use table::PlayerAction;
 
-    pub fn new_table(&mut self, name: String, table_name: String, max_players: usize, minimal_bid: i32, starting_chips: i32, key: String) {
-        self.tables.push(Table::new(name,table_name, max_players, minimal_bid, starting_chips, key));
+    fn engine_with_tables(count: usize) -> (Engine, Vec<(u64, String)>) {
+        let mut engine = Engine::new();
+        let hosts = (0..count)
+            .map(|i| engine.new_table(format!("host {}", i), format!("Table {}", i), 8, 5, 10, 1000))
+            .collect();
+        (engine, hosts)
     }
 
-    pub fn remove_table(&mut self, index: usize) {
-        self.tables.remove(index);
+    #[test]
+    fn removing_a_table_keeps_the_others_and_their_players() {
+        let (mut engine, hosts) = engine_with_tables(3);
+        let guests: Vec<String> = hosts.iter().map(|&(id, _)| engine.add_player(id, "guest".to_string(), Some(3)).unwrap()).collect();
+
+        engine.remove_table(hosts[1].0);
+        assert_eq!(engine.tables.keys().copied().collect::<Vec<_>>(), [hosts[0].0, hosts[2].0]);
+        assert_eq!(engine.find_player(&hosts[1].1), None);
+        assert_eq!(engine.find_player(&guests[1]), None);
+        for i in [0, 2] {
+            assert_eq!(engine.find_player(&hosts[i].1), Some((hosts[i].0, 0)));
+            assert_eq!(engine.find_player(&guests[i]), Some((hosts[i].0, 3)));
+        }
+
+        // Ids aren't reused by the tables created afterwards
+        let (id, _) = engine.new_table("host".to_string(), "Table 3".to_string(), 8, 5, 10, 1000);
+        assert_eq!(id, hosts[2].0 + 1);
+    }
+
+    #[test]
+    fn seat_left_mid_hand_can_be_joined_after_the_hand() {
+        let (mut engine, hosts) = engine_with_tables(1);
+        let (id, alice) = hosts[0].clone();
+        let bob = engine.add_player(id, "bob".to_string(), None).unwrap();
+        engine.add_player(id, "carol".to_string(), None).unwrap();
+        engine.tables.get_mut(&id).unwrap().start_new_game().unwrap();
+
+        engine.remove_player(&bob).unwrap();
+        assert_eq!(engine.find_player(&bob), None);
+        assert_eq!(engine.add_player(id, "dave".to_string(), Some(1)), Err(TableError::SeatTaken));
+
+        engine.tables.get_mut(&id).unwrap().player_action(0, PlayerAction::Fold).unwrap();
+        assert!(!engine.tables[&id].is_game_running());
+        let dave = engine.add_player(id, "dave".to_string(), Some(1)).unwrap();
+        assert_eq!(engine.find_player(&dave), Some((id, 1)));
+        assert_eq!(engine.find_player(&alice), Some((id, 0)));
+        assert_eq!(engine.get_player_table(&dave).unwrap().0.players[1].as_ref().unwrap().name, "dave");
     }
 }
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use serde::Serialize;
 
 use super::card::Card;
@@ -8,22 +10,60 @@ pub struct Player {
     pub cards: [Option<Card>; 2],
     pub chips: i32,
     pub current_bet: i32,
+    pub total_bet: i32,
     pub has_acted: bool,
     pub has_folded: bool,
-    pub key: String
+    /// Keeps the seat without being dealt in
+    pub is_sitting_out: bool,
+    /// Left mid-hand, the seat is freed once the hand is over
+    pub is_leaving: bool,
+    pub key: String,
+    /// Mixed into the shuffle of provably fair hands
+    pub client_seed: Option<String>,
+    /// Extra thinking time used once the table's action timeout runs out
+    pub time_bank: Duration,
 }
 
 
 impl Player {
-    pub fn new(name: String, key: String, chips: i32) -> Self {
+    pub fn new(name: String, key: String, chips: i32, time_bank: Duration) -> Self {
         Self {
             name,
             cards: [const { None }; 2],
             chips,
             current_bet: 0,
+            total_bet: 0,
             has_acted: false,
             has_folded: false,
+            is_sitting_out: false,
+            is_leaving: false,
             key,
+            client_seed: None,
+            time_bank,
         }
-    } 
+    }
+
+    /// Moves up to `amount` chips from the stack into the pot, returns how many were actually put in
+    pub fn put_chips(&mut self, amount: i32) -> i32 {
+        let amount = amount.min(self.chips);
+
+        self.chips -= amount;
+        self.current_bet += amount;
+        self.total_bet += amount;
+
+        amount
+    }
+
+    pub fn is_all_in(&self) -> bool {
+        !self.has_folded && self.chips == 0
+    }
+
+    pub fn can_act(&self) -> bool {
+        !self.has_folded && self.chips > 0
+    }
+
+    /// Whether the player gets cards when the next hand starts
+    pub fn is_dealt_in(&self) -> bool {
+        !self.is_sitting_out && self.chips > 0
+    }
 }
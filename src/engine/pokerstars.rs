@@ -0,0 +1,378 @@
+use std::fmt::Write;
+
+use chrono::DateTime;
+
+use super::{card::{Card, FullHand, HandType, Rank}, history::{HandAction, HandHistory}, table::Phase};
+
+/// Renders hands in the PokerStars hand history text format understood by tracking tools.
+/// Hands are separated by blank lines, `hero` is the key of the player whose hole cards are reported as dealt.
+pub fn export_hands<'a>(hands: impl IntoIterator<Item = &'a HandHistory>, hero: Option<&str>) -> String {
+    hands.into_iter()
+        .map(|hand| export_hand(hand, hero))
+        .collect::<Vec<_>>()
+        .join("\n\n\n")
+}
+
+pub fn export_hand(hand: &HandHistory, hero: Option<&str>) -> String {
+    let mut out = String::new();
+    let name = |seat: usize| hand.seats.iter().find(|record| record.seat == seat).map_or("", |record| record.name.as_str());
+
+    let started_at = DateTime::from_timestamp(hand.started_at, 0).unwrap_or_default();
+    writeln!(out, "PokerStars Hand #{}: Hold'em No Limit ({}/{}) - {} UTC",
+        hand.table_id * 100_000_000 + hand.hand_id,
+        hand.small_blind,
+        hand.big_blind,
+        started_at.format("%Y/%m/%d %H:%M:%S")).unwrap();
+    writeln!(out, "Table '{}' {}-max Seat #{} is the button", hand.table_name, hand.seat_count, hand.button_index + 1).unwrap();
+    for seat in &hand.seats {
+        writeln!(out, "Seat {}: {} ({} in chips)", seat.seat + 1, seat.name, seat.chips).unwrap();
+    }
+
+    let mut contributions = vec![0; hand.seat_count];
+    let mut required_bet = 0;
+    let mut phase = Phase::PreFlop;
+    for action in hand.actions.iter().filter(|action| matches!(action.action, HandAction::SmallBlind | HandAction::BigBlind)) {
+        let blind = if action.action == HandAction::SmallBlind { "small" } else { "big" };
+        writeln!(out, "{}: posts {} blind {}{}", name(action.seat), blind, action.amount, all_in_suffix(action.is_all_in)).unwrap();
+        contributions[action.seat] += action.amount;
+        required_bet = required_bet.max(action.total_bet);
+    }
+
+    writeln!(out, "*** HOLE CARDS ***").unwrap();
+    if let Some(seat) = hand.seats.iter().find(|record| Some(record.key.as_str()) == hero) {
+        if let [Some(first), Some(second)] = seat.cards {
+            writeln!(out, "Dealt to {} [{} {}]", seat.name, first, second).unwrap();
+        }
+    }
+
+    for action in hand.actions.iter().filter(|action| !matches!(action.action, HandAction::SmallBlind | HandAction::BigBlind)) {
+        while phase != action.phase {
+            phase = next_street(phase);
+            required_bet = 0;
+            write_street(&mut out, phase, &hand.board);
+        }
+
+        let player = name(action.seat);
+        let all_in = all_in_suffix(action.is_all_in);
+        match action.action {
+            HandAction::Fold => writeln!(out, "{}: folds", player),
+            HandAction::Check => writeln!(out, "{}: checks", player),
+            HandAction::Call => writeln!(out, "{}: calls {}{}", player, action.amount, all_in),
+            HandAction::Bet => writeln!(out, "{}: bets {}{}", player, action.amount, all_in),
+            HandAction::Raise => writeln!(out, "{}: raises {} to {}{}", player, action.total_bet - required_bet, action.total_bet, all_in),
+            HandAction::SmallBlind | HandAction::BigBlind => Ok(()),
+        }.unwrap();
+
+        contributions[action.seat] += action.amount;
+        if matches!(action.action, HandAction::Bet | HandAction::Raise) {
+            required_bet = action.total_bet;
+        }
+    }
+
+    // The part of the biggest bet nobody matched goes back to its owner before the pots are awarded
+    let mut sorted_contributions = contributions.clone();
+    sorted_contributions.sort_unstable_by(|a, b| b.cmp(a));
+    let uncalled = sorted_contributions[0] - sorted_contributions.get(1).copied().unwrap_or(0);
+    let top_contributor = contributions.iter().position(|&amount| amount == sorted_contributions[0]).unwrap_or(0);
+
+    let mut pots: Vec<(i32, Vec<(usize, i32)>)> = hand.awards.iter()
+        .map(|award| (award.amount, award.winners.iter().map(|winner| (winner.seat, winner.amount)).collect()))
+        .collect();
+    if uncalled > 0 {
+        if let Some(pot) = pots.iter_mut().rev().find(|(_, winners)| winners.iter().any(|&(seat, _)| seat == top_contributor)) {
+            pot.0 -= uncalled;
+            for (seat, amount) in pot.1.iter_mut() {
+                if *seat == top_contributor { *amount -= uncalled; }
+            }
+        }
+        pots.retain(|(amount, _)| *amount > 0);
+    }
+
+    let last_street = match hand.board.len() {
+        5 => Phase::River,
+        4 => Phase::Turn,
+        3 => Phase::Flop,
+        _ => Phase::PreFlop,
+    };
+    while phase != last_street {
+        phase = next_street(phase);
+        write_street(&mut out, phase, &hand.board);
+    }
+
+    if uncalled > 0 {
+        writeln!(out, "Uncalled bet ({}) returned to {}", uncalled, name(top_contributor)).unwrap();
+    }
+
+    if !hand.showdown.is_empty() {
+        writeln!(out, "*** SHOW DOWN ***").unwrap();
+        for record in &hand.showdown {
+            writeln!(out, "{}: shows {} ({})", name(record.seat), cards_to_string(&record.cards), describe_hand(&record.hand)).unwrap();
+        }
+    }
+
+    let mut won = vec![0; hand.seat_count];
+    for (i, (_, winners)) in pots.iter().enumerate() {
+        for &(seat, amount) in winners {
+            writeln!(out, "{} collected {} from {}", name(seat), amount, pot_name(pots.len(), i).to_lowercase()).unwrap();
+            won[seat] += amount;
+        }
+    }
+    if hand.showdown.is_empty() {
+        for (seat, _) in won.iter().enumerate().filter(|(_, &amount)| amount > 0) {
+            writeln!(out, "{}: doesn't show hand", name(seat)).unwrap();
+        }
+    }
+
+    writeln!(out, "*** SUMMARY ***").unwrap();
+    let total: i32 = pots.iter().map(|(amount, _)| amount).sum();
+    if pots.len() > 1 {
+        let side_pots: String = pots.iter().enumerate().skip(1)
+            .map(|(i, (amount, _))| format!(" {} {}.", pot_name(pots.len(), i), amount))
+            .collect();
+        writeln!(out, "Total pot {} Main pot {}.{} | Rake 0", total, pots[0].0, side_pots).unwrap();
+    }
+    else {
+        writeln!(out, "Total pot {} | Rake 0", total).unwrap();
+    }
+    if !hand.board.is_empty() {
+        let board: Vec<String> = hand.board.iter().map(Card::to_string).collect();
+        writeln!(out, "Board [{}]", board.join(" ")).unwrap();
+    }
+
+    for seat in &hand.seats {
+        let mut position = String::new();
+        if seat.seat == hand.button_index { position.push_str(" (button)"); }
+        for action in &hand.actions {
+            if action.seat != seat.seat { continue; }
+            if action.action == HandAction::SmallBlind { position.push_str(" (small blind)"); }
+            if action.action == HandAction::BigBlind { position.push_str(" (big blind)"); }
+        }
+
+        let fold = hand.actions.iter().find(|action| action.seat == seat.seat && action.action == HandAction::Fold);
+        let shown = hand.showdown.iter().find(|record| record.seat == seat.seat);
+        let outcome = match (fold, shown) {
+            (Some(fold), _) => {
+                let street = match fold.phase {
+                    Phase::PreFlop => "before Flop",
+                    Phase::Flop => "on the Flop",
+                    Phase::Turn => "on the Turn",
+                    _ => "on the River",
+                };
+                let didnt_bet = if contributions[seat.seat] == 0 { " (didn't bet)" } else { "" };
+                format!("folded {}{}", street, didnt_bet)
+            },
+            (None, Some(record)) if won[seat.seat] > 0 => {
+                format!("showed {} and won ({}) with {}", cards_to_string(&record.cards), won[seat.seat], describe_hand(&record.hand))
+            },
+            (None, Some(record)) => format!("showed {} and lost with {}", cards_to_string(&record.cards), describe_hand(&record.hand)),
+            (None, None) if won[seat.seat] > 0 => format!("collected ({})", won[seat.seat]),
+            (None, None) => "mucked".to_string(),
+        };
+        writeln!(out, "Seat {}: {}{} {}", seat.seat + 1, seat.name, position, outcome).unwrap();
+    }
+
+    out
+}
+
+fn next_street(phase: Phase) -> Phase {
+    match phase {
+        Phase::PreFlop => Phase::Flop,
+        Phase::Flop => Phase::Turn,
+        _ => Phase::River,
+    }
+}
+
+fn write_street(out: &mut String, phase: Phase, board: &[Card]) {
+    let cards = |range: std::ops::Range<usize>| {
+        board.get(range).unwrap_or_default().iter().map(Card::to_string).collect::<Vec<_>>().join(" ")
+    };
+    match phase {
+        Phase::Flop => writeln!(out, "*** FLOP *** [{}]", cards(0..3)),
+        Phase::Turn => writeln!(out, "*** TURN *** [{}] [{}]", cards(0..3), cards(3..4)),
+        _ => writeln!(out, "*** RIVER *** [{}] [{}]", cards(0..4), cards(4..5)),
+    }.unwrap();
+}
+
+/// Name of the `index`-th of `count` pots, side pots are only numbered when there are several
+fn pot_name(count: usize, index: usize) -> String {
+    match (count, index) {
+        (1, _) => "Pot".to_string(),
+        (_, 0) => "Main pot".to_string(),
+        (2, _) => "Side pot".to_string(),
+        (_, i) => format!("Side pot-{}", i),
+    }
+}
+
+fn all_in_suffix(is_all_in: bool) -> &'static str {
+    if is_all_in { " and is all-in" } else { "" }
+}
+
+fn cards_to_string(cards: &[Option<Card>; 2]) -> String {
+    let cards: Vec<String> = cards.iter().flatten().map(Card::to_string).collect();
+    format!("[{}]", cards.join(" "))
+}
+
+fn rank_name(rank: Rank) -> &'static str {
+    match rank {
+        Rank::Two => "Two",
+        Rank::Three => "Three",
+        Rank::Four => "Four",
+        Rank::Five => "Five",
+        Rank::Six => "Six",
+        Rank::Seven => "Seven",
+        Rank::Eight => "Eight",
+        Rank::Nine => "Nine",
+        Rank::Ten => "Ten",
+        Rank::Jack => "Jack",
+        Rank::Queen => "Queen",
+        Rank::King => "King",
+        Rank::Ace => "Ace",
+    }
+}
+
+fn plural_rank_name(rank: Rank) -> String {
+    match rank {
+        Rank::Six => "Sixes".to_string(),
+        rank => format!("{}s", rank_name(rank)),
+    }
+}
+
+/// Lowest card of the straight that has `high` on top, the wheel starts with the Ace
+fn straight_low(high: Rank) -> &'static str {
+    match high {
+        Rank::Five => "Ace",
+        Rank::Six => "Two",
+        Rank::Seven => "Three",
+        Rank::Eight => "Four",
+        Rank::Nine => "Five",
+        Rank::Ten => "Six",
+        Rank::Jack => "Seven",
+        Rank::Queen => "Eight",
+        Rank::King => "Nine",
+        _ => "Ten",
+    }
+}
+
+fn describe_hand(hand: &FullHand) -> String {
+    let ranks = &hand.ranks;
+    match hand.hand_type {
+        HandType::HighCard => format!("high card {}", rank_name(ranks[0])),
+        HandType::OnePair => format!("a pair of {}", plural_rank_name(ranks[0])),
+        HandType::TwoPair => format!("two pair, {} and {}", plural_rank_name(ranks[0]), plural_rank_name(ranks[1])),
+        HandType::ThreeOfAKind => format!("three of a kind, {}", plural_rank_name(ranks[0])),
+        HandType::Straight => format!("a straight, {} to {}", straight_low(ranks[0]), rank_name(ranks[0])),
+        HandType::Flush => format!("a flush, {} high", rank_name(ranks[0])),
+        HandType::FullHouse => format!("a full house, {} full of {}", plural_rank_name(ranks[0]), plural_rank_name(ranks[1])),
+        HandType::FourOfAKind => format!("four of a kind, {}", plural_rank_name(ranks[0])),
+        HandType::StraightFlush if ranks[0] == Rank::Ace => "a Royal Flush".to_string(),
+        HandType::StraightFlush => format!("a straight flush, {} to {}", straight_low(ranks[0]), rank_name(ranks[0])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::engine::{card::parse_cards, table::{tests::table_with_players, PlayerAction, Table}};
+
+    /// Replaces the shuffled cards of the hand being played, in its history too
+    fn deal(table: &mut Table, hole_cards: [&str; 3], board: &str) {
+        for (seat, cards) in hole_cards.into_iter().enumerate() {
+            let cards = parse_cards(cards).unwrap();
+            let cards = [Some(cards[0]), Some(cards[1])];
+            table.players[seat].as_mut().unwrap().cards = cards;
+            table.current_hand.as_mut().unwrap().seats[seat].cards = cards;
+        }
+        for (card, dealt) in table.cards.iter_mut().zip(parse_cards(board).unwrap()) {
+            *card = Some(dealt);
+        }
+    }
+
+    fn last_hand(table: &Table) -> HandHistory {
+        let mut hand = table.hand_histories.back().unwrap().clone();
+        hand.started_at = 0;
+        hand
+    }
+
+    #[test]
+    fn exports_hand_won_without_showdown() {
+        let mut table = table_with_players(6, &["alice", "bob", "carol"]);
+        table.start_new_game().unwrap();
+        deal(&mut table, ["4h 7c", "Ks 2d", "Jh 5c"], "Ad Td 8c 8d 6s");
+        table.player_action(0, PlayerAction::Raise(30)).unwrap();
+        table.player_action(1, PlayerAction::Fold).unwrap();
+        table.player_action(2, PlayerAction::Fold).unwrap();
+
+        assert_eq!(export_hand(&last_hand(&table), Some("a")), "\
+PokerStars Hand #1: Hold'em No Limit (5/10) - 1970/01/01 00:00:00 UTC
+Table 'Test' 6-max Seat #1 is the button
+Seat 1: alice (1000 in chips)
+Seat 2: bob (1000 in chips)
+Seat 3: carol (1000 in chips)
+bob: posts small blind 5
+carol: posts big blind 10
+*** HOLE CARDS ***
+Dealt to alice [4h 7c]
+alice: raises 20 to 30
+bob: folds
+carol: folds
+Uncalled bet (20) returned to alice
+alice collected 25 from pot
+alice: doesn't show hand
+*** SUMMARY ***
+Total pot 25 | Rake 0
+Seat 1: alice (button) collected (25)
+Seat 2: bob (small blind) folded before Flop
+Seat 3: carol (big blind) folded before Flop
+");
+    }
+
+    #[test]
+    fn exports_all_in_showdown_with_side_pot() {
+        let mut table = table_with_players(6, &["alice", "bob", "carol"]);
+        table.players[1].as_mut().unwrap().chips = 200;
+        table.start_new_game().unwrap();
+        deal(&mut table, ["6h 3d", "Qc As", "Ts 9s"], "Ad Td 8c 8d 6s");
+        table.player_action(0, PlayerAction::Raise(500)).unwrap();
+        table.player_action(1, PlayerAction::Call).unwrap();
+        table.player_action(2, PlayerAction::Raise(1000)).unwrap();
+        table.player_action(0, PlayerAction::Call).unwrap();
+        let mut now = Instant::now();
+        while table.is_running_out() {
+            now += Duration::from_secs(5);
+            table.deal_next_street(now);
+        }
+
+        assert_eq!(export_hand(&last_hand(&table), Some("b")), "\
+PokerStars Hand #1: Hold'em No Limit (5/10) - 1970/01/01 00:00:00 UTC
+Table 'Test' 6-max Seat #1 is the button
+Seat 1: alice (1000 in chips)
+Seat 2: bob (200 in chips)
+Seat 3: carol (1000 in chips)
+bob: posts small blind 5
+carol: posts big blind 10
+*** HOLE CARDS ***
+Dealt to bob [Qc As]
+alice: raises 490 to 500
+bob: calls 195 and is all-in
+carol: raises 500 to 1000 and is all-in
+alice: calls 500 and is all-in
+*** FLOP *** [Ad Td 8c]
+*** TURN *** [Ad Td 8c] [8d]
+*** RIVER *** [Ad Td 8c 8d] [6s]
+*** SHOW DOWN ***
+alice: shows [6h 3d] (two pair, Eights and Sixes)
+bob: shows [Qc As] (two pair, Aces and Eights)
+carol: shows [Ts 9s] (two pair, Tens and Eights)
+bob collected 600 from main pot
+carol collected 1600 from side pot
+*** SUMMARY ***
+Total pot 2200 Main pot 600. Side pot 1600. | Rake 0
+Board [Ad Td 8c 8d 6s]
+Seat 1: alice (button) showed [6h 3d] and lost with two pair, Eights and Sixes
+Seat 2: bob (small blind) showed [Qc As] and won (600) with two pair, Aces and Eights
+Seat 3: carol (big blind) showed [Ts 9s] and won (1600) with two pair, Tens and Eights
+");
+    }
+}
@@ -1,84 +1,298 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, collections::VecDeque, fmt, time::{Duration, Instant}};
+
+use chrono::Utc;
 
 use rand::prelude::*;
+use rand_chacha::ChaCha12Rng;
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
 use crate::engine::card::{compare_hands, FullHand};
 
-use super::{card::{get_best_hand, get_new_deck, Card, Color, HandType, Rank}, player::Player};
+use super::{card::{get_best_hand, get_new_deck, Card, Color, Rank}, equity::{self, Equity}, fairness::{commitment, new_server_seed, shuffled_deck, ClientSeed, FairnessRecord}, history::{ActionRecord, HandAction, HandHistory, PotAward, SeatRecord, ShowdownRecord, Winner}, player::Player};
+
+/// Fewest and most seats a table can have
+pub const MIN_SEATS: usize = 2;
+pub const MAX_SEATS: usize = 10;
+/// Finished hands kept per table, the oldest ones are dropped first
+const MAX_HAND_HISTORIES: usize = 500;
+/// Seconds a player has to act before being checked or folded automatically
+const DEFAULT_ACTION_TIMEOUT: u64 = 30;
+/// Longest action timeout a host can set, in seconds
+pub const MAX_ACTION_TIMEOUT: u64 = 60 * 60;
+/// Seconds in a fresh time bank
+const DEFAULT_TIME_BANK: u64 = 60;
+/// Largest time bank and refill a host can set, in seconds
+pub const MAX_TIME_BANK: u64 = 60 * 60;
+/// Seconds given back to every time bank each `DEFAULT_TIME_BANK_REFILL_HANDS` hands
+const DEFAULT_TIME_BANK_REFILL: u64 = 10;
+const DEFAULT_TIME_BANK_REFILL_HANDS: u64 = 10;
+/// Pause before each street is dealt once everyone left is all-in
+const RUNOUT_STREET_DELAY: Duration = Duration::from_secs(2);
+/// Most boards looked at for the all-in equities, every board is enumerated from the flop on
+const EQUITY_SAMPLES: usize = 20_000;
 
+/// Action taken by the player whose turn it is
 #[derive(Serialize, Deserialize, Clone)]
 pub enum PlayerAction {
     Fold,
+    /// Only allowed when the player already matches the required bet
     Check,
+    /// Matches the required bet, or puts in the whole stack when it's not enough
     Call,
+    /// Raises the player's total bet on this street *to* the given amount.
+    /// The increase over the required bet must be at least the last full raise (the big blind when nobody raised yet)
+    /// unless the player goes all-in. Such an incomplete all-in raise doesn't let players who already acted raise again.
     Raise(i32)
 }
 
+#[derive(Serialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TableError {
+    NotYourTurn,
+    CannotCheckFacingBet,
+    RaiseBelowMinimum,
+    ActionNotReopened,
+    InsufficientChips,
+    GameNotRunning,
+    GameAlreadyRunning,
+    NotEnoughPlayers,
+    TableFull,
+    TableNotFound,
+    SeatEmpty,
+    SeatTaken,
+    InvalidSeat,
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            TableError::NotYourTurn => "It is not your turn to act",
+            TableError::CannotCheckFacingBet => "Cannot check while facing a bet",
+            TableError::RaiseBelowMinimum => "Raise is below the minimum",
+            TableError::ActionNotReopened => "Cannot raise again after an incomplete all-in raise",
+            TableError::InsufficientChips => "Not enough chips for this action",
+            TableError::GameNotRunning => "No hand is currently being played",
+            TableError::GameAlreadyRunning => "A hand is already being played",
+            TableError::NotEnoughPlayers => "Not enough players to start a hand",
+            TableError::TableFull => "The table is full",
+            TableError::TableNotFound => "The table does not exist",
+            TableError::SeatEmpty => "Nobody sits in this seat",
+            TableError::SeatTaken => "Someone already sits in this seat",
+            TableError::InvalidSeat => "The table has no such seat",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for TableError {}
+
+/// Stage of the hand the table is in.
+/// Waiting -> PreFlop -> Flop -> Turn -> River -> Showdown, a hand won by everyone else folding
+/// ends in HandComplete instead. A new hand can be started from Waiting, Showdown or HandComplete.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Phase {
+    Waiting,
+    PreFlop,
+    Flop,
+    Turn,
+    River,
+    Showdown,
+    HandComplete,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Pot {
+    pub amount: i32,
+    pub eligible_players: Vec<usize>,
+}
+
 #[derive(Serialize, Clone, Debug)]
 pub struct Table {
+    pub id: u64,
     pub name: String,
-    pub players: [Option<Player>; 8],
+    /// One entry per seat, `max_players` long
+    pub players: Vec<Option<Player>>,
     pub revealed_cards: usize,
     pub cards: [Option<Card>; 5],
     pub pot: i32,
     pub current_required_bet: i32,
+    pub last_raise_size: i32,
     pub current_player_index: usize,
-    pub button_index: usize,
+    /// `None` until the first hand is dealt
+    pub button_index: Option<usize>,
 
-    pub is_game_running: bool,
+    pub phase: Phase,
 
-    pub minimal_bid: i32,
+    pub small_blind: i32,
+    pub big_blind: i32,
     pub max_players: usize,
     pub starting_chips: i32,
+
+    pub hand_count: u64,
+    pub current_hand: Option<HandHistory>,
+    pub hand_histories: VecDeque<HandHistory>,
+    /// Seconds each player has to act before their time bank starts running, 0 disables the timer
+    pub action_timeout: u64,
+    #[serde(skip)]
+    turn_started_at: Option<Instant>,
+    /// Seconds in a fresh time bank, also the most a bank can hold
+    pub time_bank: u64,
+    /// Seconds added to every time bank each `time_bank_refill_hands` hands
+    pub time_bank_refill: u64,
+    pub time_bank_refill_hands: u64,
+    /// Deal from the committed server seed and the players' client seeds, see `fairness`
+    pub provably_fair: bool,
+    /// Commitment to the server seed of the next provably fair hand
+    pub server_seed_hash: String,
+    #[serde(skip)]
+    server_seed: String,
+    /// Source of the per-hand seeds, a named generator rather than `StdRng` whose algorithm
+    /// may change between rand versions, so recorded seeds keep replaying the same hands
+    #[serde(skip)]
+    rng: ChaCha12Rng,
+    /// When the next street is dealt while running out the board, nobody can act meanwhile
+    #[serde(skip)]
+    next_street_at: Option<Instant>,
+    /// Equity of each seat while running out the board, empty otherwise
+    pub all_in_equities: Vec<Option<Equity>>,
+
+    #[serde(skip)]
+    pub updates: broadcast::Sender<()>,
 }
 
 
 impl Table {
-    pub fn new(host_name: String, table_name: String, max_players: usize, minimal_bid: i32, starting_chips: i32, host_key: String) -> Self {
-        let mut players = [const { None }; 8];
-        players[0] = Some(Player::new(host_name, host_key, 100));
-        Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(id: u64, host_name: String, table_name: String, max_players: usize, small_blind: i32, big_blind: i32, starting_chips: i32, host_key: String) -> Self {
+        let mut players = vec![None; max_players];
+        players[0] = Some(Player::new(host_name, host_key, starting_chips, Duration::from_secs(DEFAULT_TIME_BANK)));
+        let mut table = Self {
+            id,
             name: table_name,
             players,
             revealed_cards: 0,
             cards: [const { None }; 5],
             pot: 0,
             current_required_bet: 0,
+            last_raise_size: 0,
             current_player_index: 0,
-            button_index: 8,
-            is_game_running: false,
-            minimal_bid,
+            button_index: None,
+            phase: Phase::Waiting,
+            small_blind,
+            big_blind,
             max_players,
             starting_chips,
-        }
+            hand_count: 0,
+            current_hand: None,
+            hand_histories: VecDeque::new(),
+            action_timeout: DEFAULT_ACTION_TIMEOUT,
+            turn_started_at: None,
+            time_bank: DEFAULT_TIME_BANK,
+            time_bank_refill: DEFAULT_TIME_BANK_REFILL,
+            time_bank_refill_hands: DEFAULT_TIME_BANK_REFILL_HANDS,
+            provably_fair: false,
+            server_seed_hash: String::new(),
+            server_seed: String::new(),
+            rng: ChaCha12Rng::from_os_rng(),
+            next_street_at: None,
+            all_in_equities: Vec::new(),
+            updates: broadcast::channel(16).0,
+        };
+        table.rotate_server_seed();
+
+        table
     }
 
-    pub fn add_player(&mut self, name: String, key: String) -> bool {
-        for (i, player) in self.players.iter().enumerate() {
-            if let Some(_) = player { continue; }
-            if i > self.max_players { return false; }
+    /// Subscribes to a notification sent every time the table state changes
+    pub fn subscribe(&self) -> broadcast::Receiver<()> {
+        self.updates.subscribe()
+    }
 
-            self.players[i] = Some(Player::new(name, key, self.starting_chips));
-            return true;
-        }
+    fn notify_update(&self) {
+        // Sending only fails when nobody is listening
+        let _ = self.updates.send(());
+    }
+
+    /// Seats the player in `seat`, or in the first empty seat when `None`, and returns the seat index
+    pub fn add_player(&mut self, name: String, key: String, seat: Option<usize>) -> Result<usize, TableError> {
+        let seat_count = self.players.len();
+        let seat = match seat {
+            Some(seat) if seat >= seat_count => return Err(TableError::InvalidSeat),
+            Some(seat) if self.players[seat].is_some() => return Err(TableError::SeatTaken),
+            Some(seat) => seat,
+            None => (0..seat_count).find(|&i| self.players[i].is_none()).ok_or(TableError::TableFull)?,
+        };
+
+        let mut player = Player::new(name, key, self.starting_chips, Duration::from_secs(self.time_bank));
+        // Players joining mid-hand have no cards and sit out until the next deal
+        player.has_folded = self.is_game_running();
+        self.players[seat] = Some(player);
+        self.notify_update();
 
-        false
+        Ok(seat)
     }
 
-    pub fn remove_player(&mut self, index: usize) -> bool {
-        if let Some(_) = self.players[index] {
-            self.players[index] = None;
-            if index != 7 {
-                for i in index..7 {
-                    if let Some(player) = &self.players[i+1] {
-                        self.players[i] = Some(player.clone());
-                        self.players[i+1] = None;
-                    }
-                }
+    /// Changes the number of seats, fails if a player sits in one of the seats taken away
+    pub fn set_seat_count(&mut self, seat_count: usize) -> Result<(), TableError> {
+        if !(MIN_SEATS..=MAX_SEATS).contains(&seat_count) { return Err(TableError::InvalidSeat); }
+        if self.players.iter().skip(seat_count).any(Option::is_some) { return Err(TableError::SeatTaken); }
+
+        self.players.resize(seat_count, None);
+        self.max_players = seat_count;
+        if self.button_index.is_some_and(|button| button >= seat_count) { self.button_index = None; }
+        self.notify_update();
+
+        Ok(())
+    }
+
+    /// Takes the player out of their seat and returns them, chips they have in the pot stay there.
+    /// Mid-hand the player folds and keeps the seat until the hand is over, the rest of their stack is returned right away.
+    pub fn remove_player(&mut self, index: usize) -> Result<Player, TableError> {
+        let is_game_running = self.is_game_running();
+        let is_their_turn = index == self.current_player_index && !self.is_running_out();
+        let Some(player) = self.players.get_mut(index).and_then(Option::as_mut) else { return Err(TableError::SeatEmpty); };
+
+        if !is_game_running {
+            let removed = self.players[index].take().unwrap();
+            self.notify_update();
+            return Ok(removed);
+        }
+
+        let removed = player.clone();
+        player.chips = 0;
+        player.is_leaving = true;
+        if !player.has_folded {
+            if is_their_turn {
+                self.player_action(index, PlayerAction::Fold)?;
+            }
+            else {
+                player.has_folded = true;
+                self.record_action(index, HandAction::Fold, 0);
+
+                let turn = (self.phase, self.current_player_index);
+                self.check_for_round_end();
+                if turn != (self.phase, self.current_player_index) { self.reset_turn_timer(); }
             }
-            return true;
         }
-        return false;
+        self.notify_update();
+
+        Ok(removed)
+    }
+
+    /// Keeps the player's seat and chips but stops dealing them in, starting with the next hand
+    pub fn sit_out(&mut self, index: usize) -> Result<(), TableError> {
+        let Some(player) = self.players.get_mut(index).and_then(Option::as_mut) else { return Err(TableError::SeatEmpty); };
+        player.is_sitting_out = true;
+        self.notify_update();
+
+        Ok(())
+    }
+
+    pub fn sit_in(&mut self, index: usize) -> Result<(), TableError> {
+        let Some(player) = self.players.get_mut(index).and_then(Option::as_mut) else { return Err(TableError::SeatEmpty); };
+        player.is_sitting_out = false;
+        self.notify_update();
+
+        Ok(())
     }
 
     pub fn print(&self) {
@@ -91,7 +305,7 @@ impl Table {
         for (i, player) in self.players.iter().enumerate() {
             if let Some(player) = player {
                 println!("\t{}: {} {} {} {}", player.name, player.chips, player.current_bet, player.has_folded, {if self.current_player_index == i { "<" } else { "" }});
-                for card in player.cards.clone() {
+                for card in player.cards {
                     if let Some(card) = card {
                         println!("\t\t{:?}", card);
                     }
@@ -107,250 +321,927 @@ impl Table {
             }
         }
 
-        println!("{}", self.button_index);
+        println!("{:?}", self.button_index);
     }
     
 
-    pub fn start_new_game(&mut self) -> bool {
-        let mut current_player_count = 0;
-        for player in &self.players {
-            if let Some(_) = player {
-                current_player_count += 1;
+    pub fn is_game_running(&self) -> bool {
+        matches!(self.phase, Phase::PreFlop | Phase::Flop | Phase::Turn | Phase::River)
+    }
+
+    /// Reseeds the table's RNG, the hands dealt afterwards are the same for the same seed
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self.rotate_server_seed();
+    }
+
+    /// Draws the server seed of the next provably fair hand and publishes its commitment
+    fn rotate_server_seed(&mut self) {
+        self.server_seed = new_server_seed(&mut self.rng);
+        self.server_seed_hash = commitment(&self.server_seed);
+    }
+
+    pub fn start_new_game(&mut self) -> Result<(), TableError> {
+        let seed = self.rng.random();
+        self.start_new_game_with_seed(seed)
+    }
+
+    /// Starts a hand dealt from a deck shuffled with `seed`, replaying the seed recorded in a
+    /// hand history with the same players and actions gives back the same hand.
+    /// Provably fair tables ignore `seed` and shuffle with the committed server seed instead.
+    pub fn start_new_game_with_seed(&mut self, seed: u64) -> Result<(), TableError> {
+        if self.is_game_running() { return Err(TableError::GameAlreadyRunning); }
+
+        let current_player_count = self.players.iter().flatten().filter(|player| player.is_dealt_in()).count();
+        if current_player_count < 2 { return Err(TableError::NotEnoughPlayers); }
+
+        self.phase = Phase::PreFlop;
+        let fairness = self.provably_fair.then(|| FairnessRecord {
+            server_seed: self.server_seed.clone(),
+            commitment: self.server_seed_hash.clone(),
+            client_seeds: self.players.iter().enumerate()
+                .filter_map(|(seat, player)| Some(ClientSeed { seat, seed: player.as_ref()?.client_seed.clone()? }))
+                .collect(),
+        });
+        let deck = match &fairness {
+            Some(record) => {
+                self.rotate_server_seed();
+                shuffled_deck(&record.server_seed, &record.client_seeds)
+            },
+            None => {
+                let mut deck = get_new_deck();
+                deck.shuffle(&mut ChaCha12Rng::seed_from_u64(seed));
+                deck
+            },
+        };
+
+        self.reroll_cards(deck);
+        self.refill_time_banks();
+        self.increment_button();
+        self.start_hand_history(fairness.is_none().then_some(seed), fairness);
+        self.post_blinds();
+        self.reset_turn_timer();
+        self.notify_update();
+
+        Ok(())
+    }
+
+    /// Splits the chips put in this hand into the main pot and side pots.
+    /// Each pot can only be won by the non-folded players that contributed fully to it.
+    pub fn get_pots(&self) -> Vec<Pot> {
+        let mut levels: Vec<i32> = self.players.iter().flatten()
+            .filter(|player| !player.has_folded && player.total_bet > 0)
+            .map(|player| player.total_bet)
+            .collect();
+        levels.sort();
+        levels.dedup();
+
+        let mut pots = Vec::new();
+        let mut previous_level = 0;
+        for level in levels {
+            let mut amount = 0;
+            let mut eligible_players = Vec::new();
+            for (i, player) in self.players.iter().enumerate() {
+                if let Some(player) = player {
+                    amount += player.total_bet.min(level) - player.total_bet.min(previous_level);
+                    if !player.has_folded && player.total_bet >= level { eligible_players.push(i); }
+                }
             }
+
+            pots.push(Pot { amount, eligible_players });
+            previous_level = level;
         }
-        if current_player_count < 3 { return false }
 
-        self.is_game_running = true;
-        self.reroll_cards();
-        self.increment_button();
+        // Nobody left in the hand has bet yet, e.g. both blinds left before the first player acted
+        if pots.is_empty() && self.pot > 0 {
+            let eligible_players = self.players.iter().enumerate()
+                .filter(|(_, player)| player.as_ref().is_some_and(|player| !player.has_folded))
+                .map(|(i, _)| i)
+                .collect();
+            pots.push(Pot { amount: 0, eligible_players });
+        }
 
-        true
+        // Dead chips (folded over-bets, players that left mid-hand) go to the main pot
+        let assigned: i32 = pots.iter().map(|pot| pot.amount).sum();
+        if let Some(main_pot) = pots.first_mut() {
+            main_pot.amount += self.pot - assigned;
+        }
+
+        pots
     }
 
-    fn end_game(&mut self) {
-        let mut best_hand = FullHand{
-            hand_type: HandType::HighCard,
-            ranks: vec![ Rank::Two, Rank::Two, Rank::Two, Rank::Two, Rank::Two ],
-        };
-        let mut best_hand_player_index = 0;
-        let mut tied_indexes = Vec::new();
+    pub fn get_player_hand(&self, index: usize) -> Option<FullHand> {
+        let player = self.players[index].as_ref()?;
+        if player.has_folded { return None; }
 
-        self.is_game_running = false;
-        self.current_required_bet = 0;
+        let mut all_cards = [Card { color: Color::Heart, rank: Rank::Two }; 7];
+        for (i, card) in self.cards.iter().chain(player.cards.iter()).enumerate() {
+            all_cards[i] = (*card)?;
+        }
 
-        
+        Some(get_best_hand(all_cards))
+    }
 
-        for (i, player) in &mut self.players.iter().enumerate() {
-            if let Some(player) = player {
-                if player.has_folded { continue; }
+    fn end_game(&mut self) {
+        let remaining_count = self.players.iter().flatten().filter(|player| !player.has_folded).count();
+        self.phase = if remaining_count > 1 { Phase::Showdown } else { Phase::HandComplete };
+        self.current_required_bet = 0;
+        self.next_street_at = None;
+        self.all_in_equities.clear();
 
-                let mut all_cards = [Card { color: Color::Heart, rank: Rank::Two }; 7];
-                for i in 0..5 { all_cards[i] = self.cards[i].unwrap(); }
-                for i in 0..2 { all_cards[i+5] = player.cards[i].unwrap(); }
+        let hands: Vec<Option<FullHand>> = (0..self.players.len()).map(|i| self.get_player_hand(i)).collect();
+        let seat_count = self.players.len();
+        let first_seat = (self.button_index.unwrap_or_default() + 1) % seat_count;
+        let mut awards = Vec::new();
 
-                let result = compare_hands(&get_best_hand(all_cards), &best_hand);
+        for pot in self.get_pots() {
+            let mut winners: Vec<usize> = Vec::new();
+            for &i in &pot.eligible_players {
+                let Some(hand) = &hands[i] else { continue; };
+                let Some(&best) = winners.first() else { winners.push(i); continue; };
 
-                if result == Ordering::Greater {
-                    best_hand_player_index = i;
-                    best_hand = get_best_hand(all_cards);
-                    tied_indexes = Vec::new();
-                    continue;
+                match compare_hands(hand, hands[best].as_ref().unwrap()) {
+                    Ordering::Greater => winners = vec![i],
+                    Ordering::Equal => winners.push(i),
+                    Ordering::Less => {},
                 }
-                if result == Ordering::Equal {
-                    tied_indexes.push(i);
-                    continue;
+            }
+            if winners.is_empty() { continue; }
+
+            // Odd chips go to the winners closest to the left of the button
+            winners.sort_by_key(|&i| (i + seat_count - first_seat) % seat_count);
+            let share = pot.amount / winners.len() as i32;
+            let mut odd_chips = pot.amount % winners.len() as i32;
+            let mut award = PotAward { amount: pot.amount, winners: Vec::new() };
+            for i in winners {
+                let player = self.players[i].as_mut().unwrap();
+                let mut amount = share;
+                if odd_chips > 0 {
+                    amount += 1;
+                    odd_chips -= 1;
                 }
+                player.chips += amount;
+                award.winners.push(Winner { seat: i, amount });
             }
+            awards.push(award);
+        }
+
+        self.finish_hand_history(&hands, awards);
+
+        for player in self.players.iter_mut().flatten() {
+            player.current_bet = 0;
+            player.total_bet = 0;
         }
+        self.pot = 0;
 
-        if tied_indexes != Vec::<usize>::new() {
-            let len = tied_indexes.len();
-            for i in tied_indexes {
-                self.players[i].as_mut().unwrap().chips += self.pot / len as i32;
+        // Seats of the players who left during the hand are free again
+        for seat in self.players.iter_mut() {
+            if seat.as_ref().is_some_and(|player| player.is_leaving) { *seat = None; }
+        }
+    }
+
+    fn advance_phase(&mut self) {
+        (self.phase, self.revealed_cards) = match self.phase {
+            Phase::PreFlop => (Phase::Flop, 3),
+            Phase::Flop => (Phase::Turn, 4),
+            Phase::Turn => (Phase::River, 5),
+            _ => {
+                self.end_game();
+                return;
             }
+        };
+
+        self.current_required_bet = 0;
+        self.last_raise_size = self.big_blind;
+        for player in self.players.iter_mut().flatten() {
+            player.has_acted = false;
+            player.current_bet = 0;
         }
-        else {
-            self.players[best_hand_player_index].as_mut().unwrap().chips += self.pot;
+
+        self.current_player_index = self.button_index.unwrap_or_default();
+        self.increment_current_player();
+    }
+
+    /// Deals the board from the top of `deck`, then two cards per seat in seat order
+    fn reroll_cards(&mut self, deck: Vec<Card>) {
+        for (card, dealt) in self.cards.iter_mut().zip(&deck) {
+            *card = Some(*dealt);
         }
 
-        self.pot = 0;
+        for (i, player) in self.players.iter_mut().enumerate() {
+            let Some(player) = player else { continue; };
+            player.has_acted = false;
+            player.has_folded = !player.is_dealt_in();
+            player.current_bet = 0;
+            player.total_bet = 0;
+            player.cards = if player.has_folded { [const { None }; 2] } else { [deck.get(5 + 2 * i).copied(), deck.get(6 + 2 * i).copied()] };
+        }
+        self.revealed_cards = 0;
     }
 
-    fn reveal_next_cards(&mut self) {
-        if self.revealed_cards < 3 {
-            self.revealed_cards = 3;
+    /// Moves the button to the next player dealt into the hand
+    fn increment_button(&mut self) {
+        let seat_count = self.players.len();
+        let start = self.button_index.unwrap_or(seat_count - 1);
+        for offset in 1..=seat_count {
+            let index = (start + offset) % seat_count;
+            if self.players[index].as_ref().is_some_and(|player| !player.has_folded) {
+                self.button_index = Some(index);
+                return;
+            }
         }
-        else if self.revealed_cards < 5{
-            self.revealed_cards += 1;
+    }
+
+    /// Heads-up the button posts the small blind and acts first preflop, otherwise the two players after it post
+    fn post_blinds(&mut self) {
+        self.current_player_index = self.button_index.unwrap_or_default();
+        let is_heads_up = self.players.iter().flatten().filter(|player| !player.has_folded).count() == 2;
+        if !is_heads_up { self.increment_current_player(); }
+        self.post_blind(HandAction::SmallBlind, self.small_blind);
+        self.increment_current_player();
+        self.post_blind(HandAction::BigBlind, self.big_blind);
+        self.current_required_bet = self.big_blind;
+        self.last_raise_size = self.big_blind;
+        self.increment_current_player();
+        // The blinds can put everyone all-in
+        self.check_for_round_end();
+    }
+
+    /// Forced bet of the current player, it doesn't count as acting so the big blind keeps the option to raise
+    fn post_blind(&mut self, action: HandAction, amount: i32) {
+        let index = self.current_player_index;
+        if let Some(player) = &mut self.players[index] {
+            let amount = player.put_chips(amount);
+            self.pot += amount;
+            self.record_action(index, action, amount);
         }
-        else {
-            self.end_game();
-            return;
+    }
+
+    fn start_hand_history(&mut self, seed: Option<u64>, fairness: Option<FairnessRecord>) {
+        self.hand_count += 1;
+
+        let seats = self.players.iter().enumerate()
+            .filter_map(|(i, player)| Some((i, player.as_ref()?)))
+            .filter(|(_, player)| !player.has_folded)
+            .map(|(i, player)| SeatRecord {
+                seat: i,
+                name: player.name.clone(),
+                chips: player.chips,
+                cards: player.cards,
+                key: player.key.clone(),
+            })
+            .collect();
+
+        self.current_hand = Some(HandHistory {
+            hand_id: self.hand_count,
+            table_id: self.id,
+            table_name: self.name.clone(),
+            started_at: Utc::now().timestamp(),
+            seed,
+            fairness,
+            seat_count: self.players.len(),
+            button_index: self.button_index.unwrap_or_default(),
+            small_blind: self.small_blind,
+            big_blind: self.big_blind,
+            seats,
+            actions: Vec::new(),
+            board: Vec::new(),
+            showdown: Vec::new(),
+            awards: Vec::new(),
+        });
+    }
+
+    fn record_action(&mut self, index: usize, action: HandAction, amount: i32) {
+        let (Some(hand), Some(player)) = (&mut self.current_hand, &self.players[index]) else { return; };
+
+        hand.actions.push(ActionRecord {
+            phase: self.phase,
+            seat: index,
+            action,
+            amount,
+            total_bet: player.current_bet,
+            is_all_in: player.is_all_in(),
+        });
+    }
+
+    fn finish_hand_history(&mut self, hands: &[Option<FullHand>], awards: Vec<PotAward>) {
+        let Some(mut hand) = self.current_hand.take() else { return; };
+
+        hand.board = self.cards[..self.revealed_cards].iter().flatten().copied().collect();
+        if self.phase == Phase::Showdown {
+            for (i, full_hand) in hands.iter().enumerate() {
+                let (Some(full_hand), Some(player)) = (full_hand, &self.players[i]) else { continue; };
+                hand.showdown.push(ShowdownRecord { seat: i, cards: player.cards, hand: full_hand.clone() });
+            }
         }
+        hand.awards = awards;
 
-        self.current_required_bet = 0;
-        for player in &mut self.players {
-            if let Some(player) = player {
-                player.has_acted = false;
-                player.current_bet = 0;
+        if self.hand_histories.len() >= MAX_HAND_HISTORIES {
+            self.hand_histories.pop_front();
+        }
+        self.hand_histories.push_back(hand);
+    }
+
+    fn increment_current_player(&mut self) {
+        let seat_count = self.players.len();
+        for offset in 1..=seat_count {
+            let index = (self.current_player_index + offset) % seat_count;
+            if let Some(player) = &self.players[index] {
+                if !player.can_act() { continue; }
+                self.current_player_index = index;
+                return;
             }
         }
+    }
+
+    fn check_for_round_end(&mut self) {
+        if !self.is_game_running() { return; }
+
+        let remaining_count = self.players.iter().flatten().filter(|player| !player.has_folded).count();
+        if remaining_count <= 1 { self.end_game(); return; }
+        if self.is_running_out() { self.update_all_in_equities(); return; }
+
+        let active_players: Vec<&Player> = self.players.iter().flatten().filter(|player| player.can_act()).collect();
+        let everyone_matched = active_players.iter()
+            .all(|player| player.has_acted && player.current_bet == self.current_required_bet);
+        let nobody_to_bet_against = active_players.len() <= 1 && active_players.iter()
+            .all(|player| player.current_bet >= self.current_required_bet);
+
+        if !everyone_matched && !nobody_to_bet_against { return; }
+
+        if active_players.len() <= 1 && self.phase != Phase::River {
+            // Everyone else is all-in, run out the board one street at a time
+            self.next_street_at = Some(Instant::now() + RUNOUT_STREET_DELAY);
+            self.update_all_in_equities();
+            return;
+        }
 
-        return;
+        self.advance_phase();
     }
 
-    fn reroll_cards(&mut self) {
-        let mut rng = rand::rng();
-        let mut deck = get_new_deck();
-        deck.shuffle(&mut rng);
+    pub fn player_action(&mut self, index: usize, action: PlayerAction) -> Result<(), TableError> {
+        if !self.is_game_running() { return Err(TableError::GameNotRunning); }
+        if index != self.current_player_index || self.is_running_out() { return Err(TableError::NotYourTurn); }
 
+        let current_required_bet = self.current_required_bet;
+        let Some(player) = &mut self.players[index] else { return Err(TableError::SeatEmpty); };
 
-        for player in &mut self.players {
-            if let Some(player) = player {
-                player.has_acted = false;
-                player.has_folded = false;
-                player.current_bet = 0;
-                for i in 0..2 {
-                    let deck_clone = deck.clone();
-                    let (j, card) = deck_clone.iter().enumerate().choose(&mut rng).unwrap();
-                    deck.remove(j);
-                    player.cards[i] = Some(card.clone());
-                }
+        let (recorded_action, amount) = match action {
+            PlayerAction::Fold => {
+                player.has_folded = true;
+                player.has_acted = true;
+                (HandAction::Fold, 0)
             }
+            PlayerAction::Check => {
+                if current_required_bet > player.current_bet { return Err(TableError::CannotCheckFacingBet); }
+                player.has_acted = true;
+                (HandAction::Check, 0)
+            },
+            PlayerAction::Call => {
+                // A short stack calls all-in for less, the difference is handled by side pots
+                player.has_acted = true;
+                let amount = player.put_chips(current_required_bet - player.current_bet);
+                self.pot += amount;
+                (HandAction::Call, amount)
+            },
+            PlayerAction::Raise(val) => {
+                let is_all_in = val == player.chips + player.current_bet;
+                let raise_size = val - current_required_bet;
+                if val > player.chips + player.current_bet { return Err(TableError::InsufficientChips); }
+                if raise_size <= 0 || (raise_size < self.last_raise_size && !is_all_in) { return Err(TableError::RaiseBelowMinimum); }
+                if player.has_acted { return Err(TableError::ActionNotReopened); }
+                player.has_acted = true;
+
+                let amount = player.put_chips(val - player.current_bet);
+                self.pot += amount;
+                self.current_required_bet = val;
+
+                // Only a full raise reopens the action for everyone else
+                if raise_size >= self.last_raise_size {
+                    self.last_raise_size = raise_size;
+                    for (i, player) in self.players.iter_mut().enumerate() {
+                        if let Some(player) = player {
+                            if i != index { player.has_acted = false; }
+                        }
+                    }
+                }
+
+                let recorded_action = if current_required_bet == 0 { HandAction::Bet } else { HandAction::Raise };
+                (recorded_action, amount)
+            },
+        };
+
+        self.charge_time_bank(index);
+        self.record_action(index, recorded_action, amount);
+        self.increment_current_player();
+        self.check_for_round_end();
+        self.reset_turn_timer();
+        self.notify_update();
+        Ok(())
+    }
+
+    /// Gives the current player a fresh `action_timeout`, stops the timer between hands
+    fn reset_turn_timer(&mut self) {
+        self.turn_started_at = (self.is_game_running() && !self.is_running_out() && self.action_timeout > 0).then(Instant::now);
+    }
+
+    /// Time left for the current player to act before their time bank starts running,
+    /// `None` when nobody is on the clock
+    pub fn turn_time_remaining(&self) -> Option<Duration> {
+        let started_at = self.turn_started_at?;
+        Some(Duration::from_secs(self.action_timeout).saturating_sub(started_at.elapsed()))
+    }
+
+    /// Time the current player has spent past `action_timeout`
+    fn time_bank_used(&self, now: Instant) -> Duration {
+        let Some(started_at) = self.turn_started_at else { return Duration::ZERO; };
+        now.saturating_duration_since(started_at).saturating_sub(Duration::from_secs(self.action_timeout))
+    }
+
+    /// Time left in the player's bank, counting down while they are on the clock
+    pub fn time_bank_remaining(&self, index: usize) -> Duration {
+        let Some(player) = &self.players[index] else { return Duration::ZERO; };
+        if index != self.current_player_index { return player.time_bank; }
+
+        player.time_bank.saturating_sub(self.time_bank_used(Instant::now()))
+    }
+
+    fn charge_time_bank(&mut self, index: usize) {
+        let used = self.time_bank_used(Instant::now());
+        if let Some(player) = &mut self.players[index] {
+            player.time_bank = player.time_bank.saturating_sub(used);
         }
+    }
+
+    /// Tops up every time bank once each `time_bank_refill_hands` hands
+    fn refill_time_banks(&mut self) {
+        if self.hand_count == 0 || !self.hand_count.is_multiple_of(self.time_bank_refill_hands) { return; }
 
-        for i in 0..5 {
-            let deck_clone = deck.clone();
-            let (j, card) = deck_clone.iter().enumerate().choose(&mut rng).unwrap();
-            deck.remove(j);
-            self.cards[i] = Some(card.clone());
+        let refill = Duration::from_secs(self.time_bank_refill);
+        let maximum = Duration::from_secs(self.time_bank);
+        for player in self.players.iter_mut().flatten() {
+            player.time_bank = player.time_bank.saturating_add(refill).min(maximum);
         }
-        self.revealed_cards = 0;
+    }
 
-        return;
+    /// Whether the action is closed and the board is being dealt street by street
+    pub fn is_running_out(&self) -> bool {
+        self.next_street_at.is_some()
     }
 
-    fn increment_button(&mut self) {
-        if self.button_index == 8 { self.button_index = 0; }
-        else { self.button_index += 1; }
+    /// Deals the next street once `RUNOUT_STREET_DELAY` has passed while running out the board.
+    /// Returns whether a street was dealt.
+    pub fn deal_next_street(&mut self, now: Instant) -> bool {
+        let Some(next_street_at) = self.next_street_at else { return false; };
+        if now < next_street_at { return false; }
 
-        for (i, player) in self.players.iter().enumerate() {
-            if let Some(_) = player { continue; }
-            if self.button_index >= i { self.button_index = 0;}
-            break;
+        self.advance_phase();
+        if self.phase == Phase::River {
+            self.end_game();
+        } else {
+            self.next_street_at = Some(now + RUNOUT_STREET_DELAY);
+            self.update_all_in_equities();
         }
-        
-        self.current_player_index = self.button_index;
-        self.increment_current_player();
-        for _ in 0..=1 {
-            let _ = self.player_action(PlayerAction::Raise(5));
+        self.notify_update();
+        true
+    }
+
+    /// Chances of each non-folded player to win given the cards revealed so far
+    fn update_all_in_equities(&mut self) {
+        let (seats, hands): (Vec<usize>, Vec<[Card; 2]>) = self.players.iter().enumerate()
+            .filter_map(|(i, player)| match player.as_ref()? {
+                player if player.has_folded => None,
+                player => Some((i, [player.cards[0]?, player.cards[1]?])),
+            })
+            .unzip();
+        let board: Vec<Card> = self.cards[..self.revealed_cards].iter().flatten().copied().collect();
+
+        self.all_in_equities = vec![None; self.players.len()];
+        let Ok(equities) = equity::calculate(&hands, &board, &[], EQUITY_SAMPLES, &mut rand::rng()) else { return; };
+        for (seat, equity) in seats.into_iter().zip(equities) {
+            self.all_in_equities[seat] = Some(equity);
         }
-        return;
     }
 
-    fn increment_current_player(&mut self) {
-        if self.current_player_index == 8 { self.current_player_index = 0 }
-        else { self.current_player_index += 1; }
-        if let Some(player) = &self.players[self.current_player_index] {
-            if player.has_folded { self.increment_current_player(); }
-        }
-        else {
-            self.current_player_index = 0;
-            if let Some(player) = &self.players[self.current_player_index] {
-                if player.has_folded { self.increment_current_player(); }
-            }
+    /// Checks for the current player once their time and time bank are up, or folds when facing a bet.
+    /// Returns whether an action was taken.
+    pub fn handle_timeout(&mut self, now: Instant) -> bool {
+        let Some(started_at) = self.turn_started_at else { return false; };
+        if !self.is_game_running() { return false; }
+
+        let index = self.current_player_index;
+        let Some(player) = &self.players[index] else { return false; };
+        if !player.can_act() { return false; }
+        let deadline = started_at.checked_add(Duration::from_secs(self.action_timeout)).and_then(|deadline| deadline.checked_add(player.time_bank));
+        let Some(deadline) = deadline else { return false; };
+        if now < deadline { return false; }
+
+        let action = if player.current_bet >= self.current_required_bet { PlayerAction::Check } else { PlayerAction::Fold };
+
+        self.player_action(index, action).is_ok()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::engine::card::parse_cards;
+
+    /// Table with 5/10 blinds and 1000 chip stacks, `names` seated in order from seat 0 with their initial as key
+    pub(crate) fn table_with_players(seats: usize, names: &[&str]) -> Table {
+        let key = |name: &str| name[..1].to_string();
+        let mut table = Table::new(0, names[0].to_string(), "Test".to_string(), seats, 5, 10, 1000, key(names[0]));
+        for name in &names[1..] {
+            table.add_player(name.to_string(), key(name), None).unwrap();
         }
 
-        return;
+        table
     }
 
-    fn check_for_round_end(&mut self) {
-        if !self.is_game_running { return; }
+    #[test]
+    fn blinds_putting_everyone_all_in_go_to_showdown() {
+        let mut table = table_with_players(MIN_SEATS, &["alice", "bob"]);
+        table.players[0].as_mut().unwrap().chips = 3;
+        table.players[1].as_mut().unwrap().chips = 6;
+        table.start_new_game().unwrap();
 
-        let mut round_ended = false;
-        let mut folded_count = 0;
-        let mut total_count = 0;
-        for player in self.players.clone() {
-            if let Some(player) = player {
-                total_count += 1;
-                if player.has_folded { folded_count += 1; }
-            }
+        assert!(table.is_running_out());
+        assert!(!table.handle_timeout(Instant::now() + Duration::from_secs(table.action_timeout + table.time_bank + 1)));
+        run_out_board(&mut table);
+        assert_eq!(table.phase, Phase::Showdown);
+        assert!(table.players.iter().flatten().all(|player| !player.has_folded));
+        assert_eq!(total_chips(&table), 9);
+    }
+
+    /// Same hole cards for everyone and a fixed board, so the showdown doesn't depend on the shuffle
+    fn deal_fixed_cards(table: &mut Table, board: &str) {
+        for (card, dealt) in table.cards.iter_mut().zip(parse_cards(board).unwrap()) {
+            *card = Some(dealt);
+        }
+        let hole_cards = parse_cards("2s 3d").unwrap();
+        for player in table.players.iter_mut().flatten() {
+            player.cards = [Some(hole_cards[0]), Some(hole_cards[1])];
+        }
+    }
+
+    fn total_chips(table: &Table) -> i32 {
+        table.players.iter().flatten().map(|player| player.chips).sum::<i32>() + table.pot
+    }
+
+    fn run_out_board(table: &mut Table) {
+        let mut now = Instant::now();
+        while table.is_running_out() {
+            now += RUNOUT_STREET_DELAY;
+            table.deal_next_street(now);
         }
-        if (folded_count == total_count || folded_count == total_count - 1) && total_count != 1 { self.end_game(); return; }
+    }
 
-        for player in self.players.clone() {
-            if let Some(player) = player {
-                if !player.has_acted { break; }
-                if player.current_bet != self.current_required_bet
-                { return; }
+    fn pots(table: &Table) -> Vec<(i32, Vec<usize>)> {
+        table.get_pots().into_iter().map(|pot| (pot.amount, pot.eligible_players)).collect()
+    }
 
-                round_ended = true;
-            }
+    #[test]
+    fn contributions_split_into_side_pots() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol", "dave"]);
+        for (player, total_bet) in table.players.iter_mut().flatten().zip([100, 250, 500, 1000]) {
+            player.total_bet = total_bet;
         }
+        table.pot = 1850;
+        assert_eq!(pots(&table), [(400, vec![0, 1, 2, 3]), (450, vec![1, 2, 3]), (500, vec![2, 3]), (500, vec![3])]);
 
-        if round_ended {
-            self.current_player_index = 8;
-            self.increment_current_player();
-            self.reveal_next_cards();
+        // Bob folded after putting in more than Carol's all-in, his chips stay in the pot
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        for (player, total_bet) in table.players.iter_mut().flatten().zip([810, 310, 100]) {
+            player.total_bet = total_bet;
         }
-        return;
+        table.players[1].as_mut().unwrap().has_folded = true;
+        table.pot = 1220;
+        assert_eq!(pots(&table), [(300, vec![0, 2]), (920, vec![0])]);
     }
 
-    pub fn player_action(&mut self, action: PlayerAction) -> bool {
-        if let Some(player) = &mut self.players[self.current_player_index] {
-            match action {
-                PlayerAction::Fold => {
-                    player.has_folded = true;
-                    player.has_acted = true;
-                    
-                    self.check_for_round_end();
-                    if self.is_game_running { self.increment_current_player(); }
-                    return true;
-                }
-                PlayerAction::Check => {
-                    if self.current_required_bet == 0 {
-                        player.has_acted = true;
-                        self.check_for_round_end();
-                        if self.is_game_running { self.increment_current_player(); }
-                    }
-                    return true;
-                },
-                PlayerAction::Call => {
-                    player.has_acted = true;
-                    let additional_chips;
-                    if player.chips < self.current_required_bet - player.current_bet {
-                        additional_chips = player.chips;
-                    }
-                    else {
-                        additional_chips = self.current_required_bet - player.current_bet;
-                    }
+    #[test]
+    fn all_ins_for_different_stacks_split_into_side_pots() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol", "dave"]);
+        for (player, chips) in table.players.iter_mut().flatten().zip([100, 250, 500, 1000]) {
+            player.chips = chips;
+        }
+        table.start_new_game().unwrap();
+        deal_fixed_cards(&mut table, "2c 5c 9c Jc Kc");
 
-                    player.chips -= additional_chips;
-                    player.current_bet += additional_chips;
-                    self.pot += additional_chips;
+        table.player_action(3, PlayerAction::Raise(1000)).unwrap();
+        for seat in 0..3 {
+            table.player_action(seat, PlayerAction::Call).unwrap();
+        }
+        run_out_board(&mut table);
+        assert!(!table.is_game_running());
+        assert_eq!(total_chips(&table), 1850);
+        // Nobody matched Dave's last 500
+        assert!(table.players[3].as_ref().unwrap().chips >= 500);
+    }
 
-                    
-                    self.check_for_round_end();
-                    if self.is_game_running { self.increment_current_player(); }
-                    return true;
-                },
-                PlayerAction::Raise(val) => {
-                    if val > player.chips + player.current_bet { return false; }
-                    player.has_acted = true;
+    #[test]
+    fn folded_over_bet_stays_in_the_pot() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.players[2].as_mut().unwrap().chips = 100;
+        table.start_new_game().unwrap();
+        deal_fixed_cards(&mut table, "2c 5c 9c Jc Kc");
 
-                    player.chips -= val - player.current_bet;
-                    self.pot += val - player.current_bet;
+        for seat in 0..3 {
+            table.player_action(seat, PlayerAction::Call).unwrap();
+        }
+        assert_eq!(table.phase, Phase::Flop);
+        table.player_action(1, PlayerAction::Raise(300)).unwrap();
+        table.player_action(2, PlayerAction::Call).unwrap();
+        table.player_action(0, PlayerAction::Call).unwrap();
+        assert_eq!(table.phase, Phase::Turn);
+        table.player_action(1, PlayerAction::Check).unwrap();
+        table.player_action(0, PlayerAction::Raise(500)).unwrap();
+        table.player_action(1, PlayerAction::Fold).unwrap();
+        run_out_board(&mut table);
 
-                    player.current_bet += val - player.current_bet;
-                    self.current_required_bet += val;
+        assert!(!table.is_game_running());
+        assert_eq!(total_chips(&table), 2100);
+        assert_eq!(table.players[1].as_ref().unwrap().chips, 690);
+    }
 
+    #[test]
+    fn odd_chip_goes_left_of_the_button() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.start_new_game().unwrap();
+        // Everyone plays the royal flush on the board
+        deal_fixed_cards(&mut table, "Ah Kh Qh Jh Th");
 
-                    self.check_for_round_end();
-                    if self.is_game_running { self.increment_current_player(); }
-                    return true;
-                },
-            }
+        table.player_action(0, PlayerAction::Call).unwrap();
+        table.player_action(1, PlayerAction::Fold).unwrap();
+        table.player_action(2, PlayerAction::Call).unwrap();
+        while table.is_game_running() {
+            table.player_action(table.current_player_index, PlayerAction::Check).unwrap();
+        }
+
+        let chips: Vec<i32> = table.players.iter().flatten().map(|player| player.chips).collect();
+        assert_eq!(chips, [1002, 995, 1003]);
+        assert_eq!(total_chips(&table), 3000);
+    }
+
+    #[test]
+    fn raises_must_be_full_unless_all_in() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.start_new_game().unwrap();
+
+        assert_eq!(table.player_action(0, PlayerAction::Raise(10)), Err(TableError::RaiseBelowMinimum));
+        assert_eq!(table.player_action(0, PlayerAction::Raise(15)), Err(TableError::RaiseBelowMinimum));
+        assert_eq!(table.player_action(0, PlayerAction::Raise(1001)), Err(TableError::InsufficientChips));
+        table.player_action(0, PlayerAction::Raise(30)).unwrap();
+        // The last raise was 20 on top of the big blind
+        assert_eq!(table.player_action(1, PlayerAction::Raise(40)), Err(TableError::RaiseBelowMinimum));
+        table.player_action(1, PlayerAction::Raise(50)).unwrap();
+        table.player_action(2, PlayerAction::Call).unwrap();
+        // Bob's full raise gives Alice the option to raise again
+        table.player_action(0, PlayerAction::Raise(100)).unwrap();
+        assert_eq!(table.last_raise_size, 50);
+    }
+
+    #[test]
+    fn short_all_in_raise_does_not_reopen_the_action() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.players[2].as_mut().unwrap().chips = 45;
+        table.start_new_game().unwrap();
+
+        table.player_action(0, PlayerAction::Raise(30)).unwrap();
+        table.player_action(1, PlayerAction::Call).unwrap();
+        // All-in for 15 more, short of the 20 needed for a full raise
+        table.player_action(2, PlayerAction::Raise(45)).unwrap();
+        assert_eq!(table.last_raise_size, 20);
+
+        assert_eq!(table.player_action(0, PlayerAction::Raise(100)), Err(TableError::ActionNotReopened));
+        table.player_action(0, PlayerAction::Call).unwrap();
+        assert_eq!(table.player_action(1, PlayerAction::Raise(100)), Err(TableError::ActionNotReopened));
+        table.player_action(1, PlayerAction::Call).unwrap();
+        assert_eq!(table.phase, Phase::Flop);
+    }
+
+    fn play_seeded_hand(seed: u64) -> HandHistory {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.start_new_game_with_seed(seed).unwrap();
+
+        let actions = [PlayerAction::Raise(30), PlayerAction::Call, PlayerAction::Call];
+        for action in actions.into_iter().chain(std::iter::repeat_n(PlayerAction::Check, 9)) {
+            table.player_action(table.current_player_index, action).unwrap();
+        }
+
+        let mut history = table.hand_histories.back().unwrap().clone();
+        history.started_at = 0;
+        history
+    }
+
+    #[test]
+    fn same_seed_replays_same_hand() {
+        let first = serde_json::to_string(&play_seeded_hand(42)).unwrap();
+        let second = serde_json::to_string(&play_seeded_hand(42)).unwrap();
+        assert_eq!(first, second);
+
+        let other = serde_json::to_string(&play_seeded_hand(43)).unwrap();
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn seeded_rng_deals_same_hands() {
+        let deal = |seed| {
+            let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+            table.seed_rng(seed);
+            table.start_new_game().unwrap();
+            (table.current_hand.unwrap().seed, table.cards)
+        };
+
+        assert_eq!(deal(7), deal(7));
+    }
+
+    #[test]
+    fn timeout_folds_facing_a_bet_and_checks_otherwise() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.start_new_game().unwrap();
+        assert!(!table.handle_timeout(Instant::now()));
+
+        let later = Instant::now() + Duration::from_secs(table.action_timeout + table.time_bank + 1);
+        let first = table.current_player_index;
+        assert!(table.handle_timeout(later));
+        assert!(table.players[first].as_ref().unwrap().has_folded);
+
+        table.player_action(table.current_player_index, PlayerAction::Call).unwrap();
+        let big_blind = table.current_player_index;
+        let later = Instant::now() + Duration::from_secs(table.action_timeout + table.time_bank + 1);
+        assert!(table.handle_timeout(later));
+        assert!(!table.players[big_blind].as_ref().unwrap().has_folded);
+        assert_eq!(table.phase, Phase::Flop);
+    }
+
+    #[test]
+    fn huge_timeout_never_expires() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.action_timeout = u64::MAX;
+        table.start_new_game().unwrap();
+        assert!(!table.handle_timeout(Instant::now() + Duration::from_secs(365 * 24 * 60 * 60)));
+    }
+
+    #[test]
+    fn time_banks_refill_every_few_hands() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.players[0].as_mut().unwrap().time_bank = Duration::ZERO;
+        table.players[1].as_mut().unwrap().time_bank = Duration::from_secs(table.time_bank);
+
+        table.hand_count = table.time_bank_refill_hands - 1;
+        table.start_new_game().unwrap();
+        assert_eq!(table.players[0].as_ref().unwrap().time_bank, Duration::ZERO);
+
+        while table.is_game_running() {
+            table.player_action(table.current_player_index, PlayerAction::Fold).unwrap();
+        }
+        table.start_new_game().unwrap();
+        assert_eq!(table.players[0].as_ref().unwrap().time_bank, Duration::from_secs(table.time_bank_refill));
+        assert_eq!(table.players[1].as_ref().unwrap().time_bank, Duration::from_secs(table.time_bank));
+    }
+
+    #[test]
+    fn refilling_a_huge_time_bank_saturates() {
+        let mut table = table_with_players(8, &["alice", "bob"]);
+        table.time_bank = u64::MAX;
+        table.time_bank_refill = u64::MAX;
+        table.players[0].as_mut().unwrap().time_bank = Duration::from_secs(u64::MAX);
+        table.hand_count = table.time_bank_refill_hands - 1;
+        table.start_new_game().unwrap();
+        assert_eq!(table.players[0].as_ref().unwrap().time_bank, Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn sitting_out_players_are_skipped() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol", "dave"]);
+        table.sit_out(1).unwrap();
+        table.start_new_game().unwrap();
+
+        let bob = table.players[1].as_ref().unwrap();
+        assert!(bob.has_folded && bob.cards.iter().all(Option::is_none) && bob.total_bet == 0);
+        assert_eq!(table.button_index, Some(0));
+        assert_eq!(table.current_player_index, 0);
+
+        table.sit_out(2).unwrap();
+        table.sit_out(3).unwrap();
+        while table.is_game_running() {
+            table.player_action(table.current_player_index, PlayerAction::Fold).unwrap();
+        }
+        assert_eq!(table.start_new_game(), Err(TableError::NotEnoughPlayers));
+
+        table.sit_in(1).unwrap();
+        table.start_new_game().unwrap();
+        assert!(!table.players[1].as_ref().unwrap().has_folded);
+        assert!(table.players[2].as_ref().unwrap().has_folded);
+    }
+
+    #[test]
+    fn leaving_mid_hand_folds_and_frees_the_seat_afterwards() {
+        let mut table = table_with_players(8, &["alice"]);
+        assert_eq!(table.add_player("bob".to_string(), "b".to_string(), Some(4)), Ok(4));
+        assert_eq!(table.add_player("carol".to_string(), "c".to_string(), Some(4)), Err(TableError::SeatTaken));
+        assert_eq!(table.add_player("carol".to_string(), "c".to_string(), Some(8)), Err(TableError::InvalidSeat));
+        assert_eq!(table.add_player("carol".to_string(), "c".to_string(), None), Ok(1));
+        assert_eq!(table.add_player("dave".to_string(), "d".to_string(), Some(6)), Ok(6));
+        table.start_new_game().unwrap();
+
+        // Bob is in the big blind and leaves before it's his turn
+        assert_eq!(table.current_player_index, 6);
+        let bob = table.remove_player(4).unwrap();
+        assert_eq!(bob.chips, 990);
+        assert!(table.players[4].as_ref().unwrap().has_folded);
+        assert_eq!(table.current_player_index, 6);
+
+        table.player_action(6, PlayerAction::Fold).unwrap();
+        table.player_action(0, PlayerAction::Fold).unwrap();
+        assert!(!table.is_game_running());
+        assert!(table.players[4].is_none());
+        assert_eq!(table.players[1].as_ref().unwrap().chips, 1010);
+        assert_eq!(table.players[6].as_ref().unwrap().name, "dave");
+    }
+
+    #[test]
+    fn blinds_leaving_before_the_first_action_leave_their_chips_to_the_others() {
+        let mut table = table_with_players(8, &["alice", "bob", "carol"]);
+        table.start_new_game().unwrap();
+
+        let bob = table.remove_player(1).unwrap();
+        let carol = table.remove_player(2).unwrap();
+        assert!(!table.is_game_running());
+        assert_eq!(table.players[0].as_ref().unwrap().chips, 1015);
+        assert_eq!(total_chips(&table) + bob.chips + carol.chips, 3000);
+    }
+
+    #[test]
+    fn seat_count_is_configurable() {
+        let mut table = table_with_players(MAX_SEATS, &["alice"]);
+        assert_eq!(table.players.len(), MAX_SEATS);
+        assert_eq!(table.add_player("bob".to_string(), "b".to_string(), Some(9)), Ok(9));
+        assert_eq!(table.add_player("carol".to_string(), "c".to_string(), Some(10)), Err(TableError::InvalidSeat));
+
+        assert_eq!(table.set_seat_count(6), Err(TableError::SeatTaken));
+        assert_eq!(table.set_seat_count(MAX_SEATS + 1), Err(TableError::InvalidSeat));
+        table.remove_player(9).unwrap();
+        table.set_seat_count(MIN_SEATS).unwrap();
+        assert_eq!(table.add_player("bob".to_string(), "b".to_string(), None), Ok(1));
+        assert_eq!(table.add_player("carol".to_string(), "c".to_string(), None), Err(TableError::TableFull));
+    }
+
+    #[test]
+    fn heads_up_button_posts_small_blind_and_acts_first_preflop_only() {
+        let mut table = table_with_players(MIN_SEATS, &["alice", "bob"]);
+        table.start_new_game().unwrap();
+
+        assert_eq!(table.button_index, Some(0));
+        assert_eq!(table.players[0].as_ref().unwrap().current_bet, 5);
+        assert_eq!(table.players[1].as_ref().unwrap().current_bet, 10);
+        assert_eq!(table.current_player_index, 0);
+
+        table.player_action(0, PlayerAction::Call).unwrap();
+        table.player_action(1, PlayerAction::Check).unwrap();
+        assert_eq!(table.phase, Phase::Flop);
+        assert_eq!(table.current_player_index, 1);
+        table.player_action(1, PlayerAction::Check).unwrap();
+        table.player_action(0, PlayerAction::Check).unwrap();
+        assert_eq!(table.phase, Phase::Turn);
+
+        while table.is_game_running() {
+            table.player_action(table.current_player_index, PlayerAction::Check).unwrap();
+        }
+        table.start_new_game().unwrap();
+        assert_eq!(table.button_index, Some(1));
+        assert_eq!(table.players[1].as_ref().unwrap().current_bet, 5);
+        assert_eq!(table.current_player_index, 1);
+    }
+
+    #[test]
+    fn all_in_board_runs_out_street_by_street_with_equities() {
+        let mut table = table_with_players(MIN_SEATS, &["alice", "bob"]);
+        table.start_new_game().unwrap();
+
+        table.player_action(0, PlayerAction::Raise(1000)).unwrap();
+        table.player_action(1, PlayerAction::Call).unwrap();
+        assert!(table.is_running_out());
+        assert_eq!((table.phase, table.revealed_cards), (Phase::PreFlop, 0));
+        assert_eq!(table.turn_time_remaining(), None);
+        assert_eq!(table.player_action(table.current_player_index, PlayerAction::Check), Err(TableError::NotYourTurn));
+
+        let mut now = Instant::now();
+        assert!(!table.deal_next_street(now));
+        for revealed_cards in [3, 4] {
+            let total: f64 = table.all_in_equities.iter().flatten().map(|equity| equity.win + equity.tie / 2.0).sum();
+            assert!((total - 100.0).abs() < 1e-6);
+
+            now += RUNOUT_STREET_DELAY;
+            assert!(table.deal_next_street(now));
+            assert_eq!(table.revealed_cards, revealed_cards);
         }
-        else { self.increment_current_player(); return false; }
+
+        now += RUNOUT_STREET_DELAY;
+        assert!(table.deal_next_street(now));
+        assert!(!table.is_game_running() && !table.is_running_out());
+        assert!(table.all_in_equities.is_empty());
+        assert_eq!(table.hand_histories.back().unwrap().board.len(), 5);
     }
 }
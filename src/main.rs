@@ -1,24 +1,37 @@
+use accounts::Accounts;
+use auth::{AppState, Auth};
 use axum::{
-    http::{header::CONTENT_TYPE, Method}, routing::{post}, Router
+    http::{header::{AUTHORIZATION, CONTENT_TYPE}, Method}, routing::{get, post}, Router
 };
 use engine::Engine;
 use tower_http::cors::{Any, CorsLayer};
 use std::sync::{Arc, Mutex};
-use routes::{create_table::create_table, edit_table::edit_table, exit_table::exit_table, find_player::find_player, get_table::get_table, join_table::join_table, perform_action::perform_action, search_tables::search_tables, start_game::start_game};
+use routes::{create_table::create_table, edit_table::edit_table, exit_table::exit_table, find_player::find_player, get_table::get_table, join_table::join_table, perform_action::perform_action, search_tables::search_tables, start_game::start_game, watch_table::watch_table, register::register, login::login, get_account::get_account, get_hand_history::get_hand_history, export_hand_history::export_hand_history, set_client_seed::set_client_seed, verify_shuffle::verify_shuffle, sit_out::sit_out, sit_in::sit_in, refresh_token::refresh_token};
 
+mod accounts;
+mod auth;
 pub mod engine;
 mod routes;
+mod timer;
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
 
-    let engine = Arc::new(Mutex::new(Engine::new()));
+    let state = AppState {
+        engine: Arc::new(Mutex::new(Engine::new())),
+        auth: Arc::new(Auth::from_env()),
+        accounts: Arc::new(Accounts::from_env().expect("Failed to open the accounts database")),
+    };
+    let refunded = state.accounts.refund_open_stacks().expect("Failed to refund the open stacks");
+    if refunded > 0 { println!("Refunded {} stacks left on the tables at the last shutdown", refunded); }
+    tokio::spawn(timer::run_turn_timer(state.engine.clone()));
+    tokio::spawn(timer::run_stack_sync(state.engine.clone(), state.accounts.clone()));
 
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods([Method::POST])
-        .allow_headers([CONTENT_TYPE]);
+        .allow_headers([CONTENT_TYPE, AUTHORIZATION]);
 
     let app = Router::new()
         .route("/get_table", post(get_table))
@@ -30,7 +43,18 @@ async fn main() {
         .route("/search", post(search_tables))
         .route("/start", post(start_game))
         .route("/find", post(find_player))
-        .with_state(engine)
+        .route("/ws", get(watch_table))
+        .route("/register", post(register))
+        .route("/login", post(login))
+        .route("/account", post(get_account))
+        .route("/history", post(get_hand_history))
+        .route("/history/pokerstars", post(export_hand_history))
+        .route("/seed", post(set_client_seed))
+        .route("/verify", post(verify_shuffle))
+        .route("/sit_out", post(sit_out))
+        .route("/sit_in", post(sit_in))
+        .route("/refresh", post(refresh_token))
+        .with_state(state)
         .layer(cors);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
@@ -1,26 +1,51 @@
 use std::sync::{Arc, Mutex};
 
-use axum::{extract::State, Json};
-use chrono::Utc;
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}, Json};
 use serde::Deserialize;
-use crate::engine::Engine;
+use crate::{accounts::Accounts, auth::{AccountClaims, Auth}, engine::{table::{MAX_ACTION_TIMEOUT, MAX_SEATS, MIN_SEATS}, Engine}};
 
 pub async fn create_table(
     State(engine): State<Arc<Mutex<Engine>>>,
-    Json(JoinRequest { name, table_name, max_players, minimal_bid, starting_chips }): Json<JoinRequest>,
-) -> String {
-    let key = name.clone() + &Utc::now().to_string();
+    State(auth): State<Arc<Auth>>,
+    State(accounts): State<Arc<Accounts>>,
+    AccountClaims { account, name, .. }: AccountClaims,
+    Json(JoinRequest { table_name, max_players, small_blind, big_blind, starting_chips, provably_fair, action_timeout }): Json<JoinRequest>,
+) -> Result<String, Response> {
+    if small_blind <= 0 || big_blind < small_blind || starting_chips <= 0 || !(MIN_SEATS..=MAX_SEATS).contains(&max_players)
+    || action_timeout.is_some_and(|timeout| timeout > MAX_ACTION_TIMEOUT) {
+        return Err(StatusCode::BAD_REQUEST.into_response());
+    }
 
-    engine.lock().unwrap().new_table(name, table_name, max_players, minimal_bid, starting_chips, key.clone());
+    // The host buys in with the table's starting chips
+    accounts.run(move |accounts| accounts.withdraw(account, starting_chips as i64)).await.map_err(IntoResponse::into_response)?;
+    let (id, key) = {
+        let mut engine = engine.lock().unwrap();
+        let (id, key) = engine.new_table(name.clone(), table_name, max_players, small_blind, big_blind, starting_chips);
+        if let Some(table) = engine.tables.get_mut(&id) {
+            table.provably_fair = provably_fair;
+            table.action_timeout = action_timeout.unwrap_or(table.action_timeout);
+        }
+        (id, key)
+    };
 
-    key
+    let stack_key = key.clone();
+    if let Err(error) = accounts.run(move |accounts| accounts.open_stack(account, &stack_key, starting_chips as i64)).await {
+        engine.lock().unwrap().remove_table(id);
+        accounts.run(move |accounts| accounts.deposit(account, starting_chips as i64)).await.map_err(IntoResponse::into_response)?;
+        return Err(error.into_response());
+    }
+
+    Ok(auth.issue_token(key, account, name, id, 0))
 }
 
 #[derive(Deserialize)]
 pub struct JoinRequest {
-    name: String,
     table_name: String,
     max_players: usize,
-    minimal_bid: i32,
+    small_blind: i32,
+    big_blind: i32,
     starting_chips: i32,
+    #[serde(default)]
+    provably_fair: bool,
+    action_timeout: Option<u64>,
 }
@@ -1,44 +1,54 @@
 use std::sync::{Arc, Mutex};
 
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}, Json};
 use serde::Deserialize;
-use crate::engine::Engine;
+use crate::{auth::Claims, engine::{table::{MAX_ACTION_TIMEOUT, MAX_SEATS, MAX_TIME_BANK, MIN_SEATS}, Engine}};
 
 pub async fn edit_table(
     State(engine): State<Arc<Mutex<Engine>>>,
+    claims: Claims,
     Json(settings): Json<TableSettings>,
-) -> StatusCode {
+) -> Result<StatusCode, Response> {
     let mut engine = engine.lock().unwrap();
-    for table in engine.get_tables() {
-        for player in table.players.iter() {
-            if let Some(player) = player {
-                if player.key != settings.key { continue; }
+    let Some((table, _)) = engine.get_player_table(&claims.key) else { return Err(StatusCode::UNAUTHORIZED.into_response()); };
 
-                if settings.minimal_bid <= 0
-                || settings.max_players <= 0
-                || settings.max_players >= 8
-                || settings.starting_chips <= 0 {
-                    return StatusCode::BAD_REQUEST;
-                }
-
-                table.name = settings.name;
-                table.minimal_bid = settings.minimal_bid;
-                table.max_players = settings.max_players;
-                table.starting_chips = settings.starting_chips;
-
-                return StatusCode::ACCEPTED;
-            }
-        }
+    if settings.small_blind <= 0
+    || settings.big_blind < settings.small_blind
+    || !(MIN_SEATS..=MAX_SEATS).contains(&settings.max_players)
+    || settings.starting_chips <= 0
+    || settings.action_timeout.is_some_and(|timeout| timeout > MAX_ACTION_TIMEOUT)
+    || settings.time_bank.is_some_and(|time_bank| time_bank > MAX_TIME_BANK)
+    || settings.time_bank_refill.is_some_and(|refill| refill > MAX_TIME_BANK)
+    || settings.time_bank_refill_hands == Some(0) {
+        return Err(StatusCode::BAD_REQUEST.into_response());
     }
 
-    StatusCode::UNAUTHORIZED
+    // The only setting that can still fail, so the table is left untouched when it does
+    table.set_seat_count(settings.max_players).map_err(IntoResponse::into_response)?;
+    table.name = settings.name;
+    table.small_blind = settings.small_blind;
+    table.big_blind = settings.big_blind;
+    table.starting_chips = settings.starting_chips;
+    table.provably_fair = settings.provably_fair;
+    table.action_timeout = settings.action_timeout.unwrap_or(table.action_timeout);
+    table.time_bank = settings.time_bank.unwrap_or(table.time_bank);
+    table.time_bank_refill = settings.time_bank_refill.unwrap_or(table.time_bank_refill);
+    table.time_bank_refill_hands = settings.time_bank_refill_hands.unwrap_or(table.time_bank_refill_hands);
+
+    Ok(StatusCode::ACCEPTED)
 }
 
 #[derive(Deserialize)]
 pub struct TableSettings {
-    key: String,
     name: String,
-    minimal_bid: i32,
+    small_blind: i32,
+    big_blind: i32,
     max_players: usize,
     starting_chips: i32,
+    #[serde(default)]
+    provably_fair: bool,
+    action_timeout: Option<u64>,
+    time_bank: Option<u64>,
+    time_bank_refill: Option<u64>,
+    time_bank_refill_hands: Option<u64>,
 }
\ No newline at end of file
@@ -1,38 +1,22 @@
 use std::sync::{Arc, Mutex};
 
-use axum::{extract::State, http::StatusCode, Json};
-use serde::Deserialize;
-use crate::engine::Engine;
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}};
+use crate::{accounts::Accounts, auth::Claims, engine::Engine};
 
 pub async fn exit_table(
     State(engine): State<Arc<Mutex<Engine>>>,
-    Json(Key { key }): Json<Key>,
-) -> StatusCode {
-    let mut engine = engine.lock().unwrap();
-
-    for (j, table) in engine.get_tables().iter_mut().enumerate() {
-        for (i, player) in table.players.iter().enumerate() {
-            if let Some(player) = player {
-                if player.key != key { continue; }
-
-                let result = table.remove_player(i);
-                if !result { return StatusCode::INTERNAL_SERVER_ERROR; }
-
-                let mut current_players = 0;
-                for player in table.players.iter().enumerate() {
-                    if let Some(_) = player { current_players += 1; }
-                }
-                if current_players == 0 { engine.remove_table(j); }
-
-                return StatusCode::ACCEPTED;
-            }
-        }
-    }
-
-    StatusCode::UNAUTHORIZED
+    State(accounts): State<Arc<Accounts>>,
+    claims: Claims,
+) -> Result<StatusCode, Response> {
+    let player = {
+        let mut engine = engine.lock().unwrap();
+        if engine.find_player(&claims.key).is_none() { return Err(StatusCode::UNAUTHORIZED.into_response()); }
+        engine.remove_player(&claims.key).map_err(IntoResponse::into_response)?
+    };
+
+    // Cash out whatever is left in front of the player
+    let Claims { account, key, .. } = claims;
+    accounts.run(move |accounts| accounts.close_stack(account, &key, player.chips as i64)).await.map_err(IntoResponse::into_response)?;
+
+    Ok(StatusCode::ACCEPTED)
 }
-
-#[derive(Deserialize)]
-pub struct Key {
-    key: String
-}
\ No newline at end of file
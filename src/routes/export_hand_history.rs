@@ -0,0 +1,34 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, http::{header, StatusCode}, response::IntoResponse, Json};
+use serde::Deserialize;
+use crate::{auth::Claims, engine::{pokerstars::export_hands, Engine}};
+
+/// Finished hands of the player's table as a PokerStars hand history file,
+/// optionally limited to the hand ids between `from` and `to`
+pub async fn export_hand_history(
+    State(engine): State<Arc<Mutex<Engine>>>,
+    claims: Claims,
+    Json(payload): Json<ExportRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut engine = engine.lock().unwrap();
+    let (table, _) = engine.get_player_table(&claims.key).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let hands: Vec<_> = table.hand_histories.iter()
+        .filter(|hand| payload.from.is_none_or(|from| hand.hand_id >= from))
+        .filter(|hand| payload.to.is_none_or(|to| hand.hand_id <= to))
+        .map(|hand| hand.seen_by(&claims.key))
+        .collect();
+
+    let filename = format!("attachment; filename=\"table-{}.txt\"", table.id);
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()), (header::CONTENT_DISPOSITION, filename)],
+        export_hands(&hands, Some(&claims.key)),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ExportRequest {
+    from: Option<u64>,
+    to: Option<u64>,
+}
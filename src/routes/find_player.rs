@@ -1,34 +1,20 @@
 use std::sync::{Arc, Mutex};
 
 use axum::{extract::State, Json};
-use serde::{Deserialize, Serialize};
-use crate::engine::Engine;
+use serde::Serialize;
+use crate::{auth::Claims, engine::Engine};
 
 pub async fn find_player (
     State(engine): State<Arc<Mutex<Engine>>>,
-    Json(Key { key }): Json<Key>,
+    claims: Claims,
 ) -> Json<FindResponse> {
-    let mut engine = engine.lock().unwrap();
+    let table = engine.lock().unwrap().find_player(&claims.key).map(|(id, _)| id);
 
-    for (i, table) in engine.get_tables().iter().enumerate() {
-        for player in table.players.clone() {
-            if let Some(player) = player {
-                if player.key != key { continue; }
-                return Json(FindResponse { table: i as i32 });
-            }
-        }
-    }
-
-    return Json(FindResponse { table: -1 as i32 })
+    Json(FindResponse { table })
 }
 
 
-#[derive(Deserialize)]
-pub struct Key {
-    pub key: String
-}
-
 #[derive(Serialize)]
 pub struct FindResponse {
-    table: i32
+    table: Option<u64>
 }
\ No newline at end of file
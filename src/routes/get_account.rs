@@ -0,0 +1,11 @@
+use std::sync::Arc;
+
+use axum::{extract::State, response::{IntoResponse, Response}, Json};
+use crate::{accounts::{Account, Accounts}, auth::AccountClaims};
+
+pub async fn get_account(
+    State(accounts): State<Arc<Accounts>>,
+    AccountClaims { account, .. }: AccountClaims,
+) -> Result<Json<Account>, Response> {
+    accounts.run(move |accounts| accounts.get_account(account)).await.map(Json).map_err(IntoResponse::into_response)
+}
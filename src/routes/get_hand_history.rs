@@ -0,0 +1,15 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, http::StatusCode, Json};
+use crate::{auth::Claims, engine::{history::HandHistory, Engine}};
+
+/// Finished hands of the player's table, oldest first
+pub async fn get_hand_history(
+    State(engine): State<Arc<Mutex<Engine>>>,
+    claims: Claims,
+) -> Result<Json<Vec<HandHistory>>, StatusCode> {
+    let mut engine = engine.lock().unwrap();
+    let (table, _) = engine.get_player_table(&claims.key).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    Ok(Json(table.hand_histories.iter().map(|hand| hand.seen_by(&claims.key)).collect()))
+}
@@ -1,104 +1,101 @@
 use std::sync::{Arc, Mutex};
 
 use axum::{extract::State, http::StatusCode, Json};
-use serde::{Deserialize, Serialize};
-use crate::engine::{card::{get_best_hand, Card, Color, FullHand, Rank}, Engine};
+use serde::Serialize;
+use crate::{auth::Claims, engine::{card::{Card, FullHand}, equity::Equity, table::{Phase, Pot, Table}, Engine}};
 
 
 pub async fn get_table(
     State(engine): State<Arc<Mutex<Engine>>>,
-    Json(Key { key }): Json<Key>
+    claims: Claims,
 ) -> Result<Json<PubTable>, StatusCode> {
-    for table in engine.lock().unwrap().get_tables() {
-        if table.players.iter().any(|e| {if let Some(player) = e { player.key == key } else { false }}) {
-            let mut players = [const { None }; 8];
-            let mut revealed_cards = [const { None }; 5];
-            let mut is_first_game = false;
-            let mut player_index = 10;
-            for (i, player) in table.players.iter().enumerate() {
-                if let Some(player) = player {
-                    if key == player.key { player_index = i; }
+    let mut engine = engine.lock().unwrap();
+    let (table, _) = engine.get_player_table(&claims.key).ok_or(StatusCode::NOT_FOUND)?;
 
-                    let mut all_cards = [Card { color: Color::Heart, rank: Rank::Two }; 7];
-                    for i in 0..5 { 
-                        if let Some(card) = table.cards[i] {
-                            all_cards[i] = card;
-                        }
-                        else {
-                            is_first_game = true;
-                        }
-                    }
-                    for i in 0..2 {
-                        if let Some(card) = player.cards[i] {
-                            all_cards[i+5] = card;
-                        }
-                        else {
-                            is_first_game = true;
-                        }
-                    }
-                    
-                    if table.is_game_running || is_first_game {
-                        players[i] = Some(PubPlayer {
-                            name: player.name.clone(),
-                            current_bet: player.current_bet,
-                            chips: player.chips,
-                            has_folded: player.has_folded,
-                            cards: {if key == player.key { player.cards.clone() } else { [const { None }; 2] }},
-                            best_hand: None,
-                        });
-                        continue;
-                    }
+    get_pub_table(table, &claims.key).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Builds the view of `table` seen by the player holding `key`, `None` if they don't sit at it
+pub fn get_pub_table(table: &Table, key: &str) -> Option<PubTable> {
+    let player_index = table.players.iter().position(|player| player.as_ref().is_some_and(|player| player.key == key))?;
+    let mut revealed_cards = [const { None }; 5];
+    let players = table.players.iter().enumerate().map(|(i, player)| {
+        let player = player.as_ref()?;
+
+        // Hands are turned face up at showdown and when everyone left is all-in
+        let is_shown = (table.phase == Phase::Showdown || table.is_running_out()) && !player.has_folded;
+        Some(PubPlayer {
+            name: player.name.clone(),
+            current_bet: player.current_bet,
+            chips: player.chips,
+            has_folded: player.has_folded,
+            is_sitting_out: player.is_sitting_out,
+            cards: {if is_shown || key == player.key { player.cards } else { [const { None }; 2] }},
+            best_hand: {if is_shown && table.phase == Phase::Showdown { table.get_player_hand(i) } else { None }},
+            time_bank: table.time_bank_remaining(i).as_millis() as u64,
+            equity: table.all_in_equities.get(i).copied().flatten(),
+        })
+    }).collect();
 
-                    players[i] = Some(PubPlayer {
-                        name: player.name.clone(),
-                        current_bet: player.current_bet,
-                        chips: player.chips,
-                        has_folded: player.has_folded,
-                        cards: player.cards.clone(),
-                        best_hand: Some(get_best_hand(all_cards)),
-                    });
-                }
-            }
-        
-            for i in 0..table.revealed_cards {
-                revealed_cards[i as usize] = table.cards[i as usize].clone();
-            }
-        
-        
-            return Ok(Json(PubTable {
-                name: table.name.clone(),
-                player: player_index,
-                players,
-                revealed_cards,
-                pot: table.pot,
-                current_required_bet: table.current_required_bet,
-                current_player_index: table.current_player_index,
-                button_index: table.button_index,
-                is_game_running: table.is_game_running,
-                minimal_bid: table.minimal_bid,
-                max_players: table.max_players,
-                starting_chips: table.starting_chips,
-            }));
-        }
-    }
+    revealed_cards[..table.revealed_cards].copy_from_slice(&table.cards[..table.revealed_cards]);
 
-    Err(StatusCode::NOT_FOUND)
+
+    Some(PubTable {
+        id: table.id,
+        name: table.name.clone(),
+        player: player_index,
+        players,
+        revealed_cards,
+        pot: table.pot,
+        pots: table.get_pots(),
+        current_required_bet: table.current_required_bet,
+        minimum_raise: table.current_required_bet + table.last_raise_size,
+        current_player_index: table.current_player_index,
+        button_index: table.button_index,
+        phase: table.phase,
+        is_game_running: table.is_game_running(),
+        small_blind: table.small_blind,
+        big_blind: table.big_blind,
+        max_players: table.max_players,
+        starting_chips: table.starting_chips,
+        provably_fair: table.provably_fair,
+        server_seed_hash: table.server_seed_hash.clone(),
+        action_timeout: table.action_timeout,
+        time_bank: table.time_bank,
+        time_bank_refill: table.time_bank_refill,
+        time_bank_refill_hands: table.time_bank_refill_hands,
+        turn_time_remaining: table.turn_time_remaining().map(|remaining| remaining.as_millis() as u64),
+    })
 }
 
 #[derive(Serialize)]
 pub struct PubTable {
+    id: u64,
     name: String,
     player: usize,
-    players: [Option<PubPlayer>; 8],
+    players: Vec<Option<PubPlayer>>,
     revealed_cards: [Option<Card>; 5],
     pot: i32,
+    pots: Vec<Pot>,
     current_required_bet: i32,
+    minimum_raise: i32,
     current_player_index: usize,
-    button_index: usize,
+    button_index: Option<usize>,
+    phase: Phase,
     is_game_running: bool,
-    minimal_bid: i32,
+    small_blind: i32,
+    big_blind: i32,
     max_players: usize,
     starting_chips: i32,
+    provably_fair: bool,
+    /// Commitment to the server seed of the next provably fair hand
+    server_seed_hash: String,
+    action_timeout: u64,
+    time_bank: u64,
+    time_bank_refill: u64,
+    time_bank_refill_hands: u64,
+    /// Milliseconds left for the current player to act
+    turn_time_remaining: Option<u64>,
 }
 
 #[derive(Serialize)]
@@ -107,11 +104,11 @@ pub struct PubPlayer {
     chips: i32,
     current_bet: i32,
     has_folded: bool,
+    is_sitting_out: bool,
     cards: [Option<Card>; 2],
     best_hand: Option<FullHand>,
-}
-
-#[derive(Deserialize)]
-pub struct Key {
-    pub key: String
+    /// Milliseconds left in the time bank
+    time_bank: u64,
+    /// Chances to win while the board is run out after everyone went all-in
+    equity: Option<Equity>,
 }
\ No newline at end of file
@@ -1,29 +1,50 @@
 use std::sync::{Arc, Mutex};
 
-use axum::{extract::State, http::StatusCode, Json};
-use chrono::Utc;
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}, Json};
 use serde::Deserialize;
-use crate::engine::Engine;
+use crate::{accounts::Accounts, auth::{AccountClaims, Auth}, engine::{table::TableError, Engine}};
 
 pub async fn join_table(
     State(engine): State<Arc<Mutex<Engine>>>,
-    Json(JoinRequest { name , table}): Json<JoinRequest>,
-) -> Result<String, StatusCode> {
-    let key = name.clone() + &Utc::now().to_string();
-    
-    if let Some(table) = engine.lock().unwrap().get_tables().get_mut(table) {
-        let result = table.add_player(name, key.clone());
-        if !result { return Err(StatusCode::INTERNAL_SERVER_ERROR); }
-    }
-    else {
-        return Err(StatusCode::FORBIDDEN);
+    State(auth): State<Arc<Auth>>,
+    State(accounts): State<Arc<Accounts>>,
+    AccountClaims { account, name, .. }: AccountClaims,
+    Json(JoinRequest { table, seat }): Json<JoinRequest>,
+) -> Result<String, Response> {
+    let buy_in = engine.lock().unwrap().get_tables().get(&table).ok_or(TableError::TableNotFound.into_response())?.starting_chips;
+    accounts.run(move |accounts| accounts.withdraw(account, buy_in as i64)).await.map_err(IntoResponse::into_response)?;
+
+    let joined = {
+        let mut engine = engine.lock().unwrap();
+        // The buy-in may have been edited while the bankroll was charged
+        match engine.get_tables().get(&table).map(|table| table.starting_chips) {
+            None => Err(TableError::TableNotFound.into_response()),
+            Some(starting_chips) if starting_chips != buy_in => Err(StatusCode::CONFLICT.into_response()),
+            Some(_) => engine.add_player(table, name.clone(), seat).map_err(IntoResponse::into_response),
+        }
+    };
+    let key = match joined {
+        Ok(key) => key,
+        Err(response) => {
+            accounts.run(move |accounts| accounts.deposit(account, buy_in as i64)).await.map_err(IntoResponse::into_response)?;
+            return Err(response);
+        }
+    };
+
+    let stack_key = key.clone();
+    if let Err(error) = accounts.run(move |accounts| accounts.open_stack(account, &stack_key, buy_in as i64)).await {
+        engine.lock().unwrap().remove_player(&key).map_err(IntoResponse::into_response)?;
+        accounts.run(move |accounts| accounts.deposit(account, buy_in as i64)).await.map_err(IntoResponse::into_response)?;
+        return Err(error.into_response());
     }
-    
-    Ok(key)
+    let seat = engine.lock().unwrap().find_player(&key).ok_or(TableError::SeatEmpty.into_response())?.1;
+
+    Ok(auth.issue_token(key, account, name, table, seat))
 }
 
 #[derive(Deserialize)]
 pub struct JoinRequest {
-    name: String,
-    table: usize,
-}
\ No newline at end of file
+    table: u64,
+    /// Seat to sit in, the first empty one when left out
+    seat: Option<usize>,
+}
@@ -0,0 +1,16 @@
+use std::sync::Arc;
+
+use axum::{extract::State, response::{IntoResponse, Response}, Json};
+use crate::{accounts::Accounts, auth::Auth};
+
+use super::register::Credentials;
+
+pub async fn login(
+    State(auth): State<Arc<Auth>>,
+    State(accounts): State<Arc<Accounts>>,
+    Json(Credentials { name, password }): Json<Credentials>,
+) -> Result<String, Response> {
+    let account = accounts.run(move |accounts| accounts.login(&name, &password)).await.map_err(IntoResponse::into_response)?;
+
+    Ok(auth.issue_account_token(account.id, account.name))
+}
@@ -1,3 +1,8 @@
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::Serialize;
+
+use crate::{accounts::AccountError, engine::table::TableError};
+
 pub mod search_tables;
 pub mod get_table;
 pub mod join_table;
@@ -7,3 +12,56 @@ pub mod exit_table;
 pub mod edit_table;
 pub mod create_table;
 pub mod find_player;
+pub mod watch_table;
+pub mod register;
+pub mod login;
+pub mod get_account;
+pub mod get_hand_history;
+pub mod export_hand_history;
+pub mod set_client_seed;
+pub mod verify_shuffle;
+pub mod sit_out;
+pub mod sit_in;
+pub mod refresh_token;
+
+#[derive(Serialize)]
+pub struct ErrorResponse<E> {
+    error: E,
+    message: String,
+}
+
+impl IntoResponse for TableError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            TableError::CannotCheckFacingBet
+            | TableError::RaiseBelowMinimum
+            | TableError::ActionNotReopened
+            | TableError::InsufficientChips
+            | TableError::InvalidSeat => StatusCode::BAD_REQUEST,
+            TableError::NotYourTurn
+            | TableError::GameNotRunning
+            | TableError::GameAlreadyRunning
+            | TableError::TableFull
+            | TableError::SeatTaken
+            | TableError::NotEnoughPlayers => StatusCode::CONFLICT,
+            TableError::TableNotFound
+            | TableError::SeatEmpty => StatusCode::NOT_FOUND,
+        };
+
+        (status, Json(ErrorResponse { error: self, message: self.to_string() })).into_response()
+    }
+}
+
+impl IntoResponse for AccountError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            AccountError::NameTaken => StatusCode::CONFLICT,
+            AccountError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            AccountError::AccountNotFound => StatusCode::NOT_FOUND,
+            AccountError::InsufficientBankroll => StatusCode::PAYMENT_REQUIRED,
+            AccountError::Database => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status, Json(ErrorResponse { error: self, message: self.to_string() })).into_response()
+    }
+}
@@ -1,47 +1,24 @@
 use std::sync::{Arc, Mutex};
 
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}, Json};
 use serde::{Deserialize, Serialize};
-use crate::engine::{table::PlayerAction, Engine};
+use crate::{auth::Claims, engine::{table::PlayerAction, Engine}};
 
 pub async fn perform_action(
     State(engine): State<Arc<Mutex<Engine>>>,
-    Json(ActionRequest{ action, key} ): Json<ActionRequest>,
-) -> StatusCode {
-    let mut i = 0;
-    let mut found_player = false;
-
+    claims: Claims,
+    Json(ActionRequest{ action } ): Json<ActionRequest>,
+) -> Result<StatusCode, Response> {
     let mut engine = engine.lock().unwrap();
+    let Some((table, seat)) = engine.get_player_table(&claims.key) else { return Err(StatusCode::UNAUTHORIZED.into_response()); };
 
-    for table in engine.get_tables() {
-        for (j, player) in table.players.iter().enumerate() {
-            if let Some(player) = player {
-                if player.key != key { continue; }
-                if j != table.current_player_index { continue; }
-                found_player = true;
-                break;
-            }
-        }
-        if found_player { break; }
-        i += 1;
-    }
-    if found_player {
-        if let Some(table) = engine.get_tables().get_mut(i) {
-            let result = table.player_action(action);
-            if !result {
-                return StatusCode::INTERNAL_SERVER_ERROR;
-            }
-        } else {
-            return StatusCode::INTERNAL_SERVER_ERROR;
-        }
-    }
+    table.player_action(seat, action).map_err(IntoResponse::into_response)?;
 
-    StatusCode::ACCEPTED
+    Ok(StatusCode::ACCEPTED)
 }
 
 
 #[derive(Deserialize, Serialize)]
 pub struct ActionRequest {
-    key: String,
     action: PlayerAction,
-}
\ No newline at end of file
+}
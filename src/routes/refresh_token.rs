@@ -0,0 +1,26 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Deserialize;
+use crate::{auth::{AccountClaims, Auth, Claims}, engine::Engine};
+
+/// Trades a table token, expired or not, for a fresh one while the player is still seated.
+/// The account token proves who is asking, since the table token's expiry isn't checked.
+pub async fn refresh_token(
+    State(engine): State<Arc<Mutex<Engine>>>,
+    State(auth): State<Arc<Auth>>,
+    AccountClaims { account, .. }: AccountClaims,
+    Json(RefreshRequest { token }): Json<RefreshRequest>,
+) -> Result<String, StatusCode> {
+    let claims: Claims = auth.verify_signature(&token).ok_or(StatusCode::UNAUTHORIZED)?;
+    if claims.account != account { return Err(StatusCode::UNAUTHORIZED); }
+
+    let (table, seat) = engine.lock().unwrap().find_player(&claims.key).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    Ok(auth.issue_token(claims.key, account, claims.name, table, seat))
+}
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    token: String,
+}
@@ -0,0 +1,21 @@
+use std::sync::Arc;
+
+use axum::{extract::State, response::{IntoResponse, Response}, Json};
+use serde::Deserialize;
+use crate::{accounts::Accounts, auth::Auth};
+
+pub async fn register(
+    State(auth): State<Arc<Auth>>,
+    State(accounts): State<Arc<Accounts>>,
+    Json(Credentials { name, password }): Json<Credentials>,
+) -> Result<String, Response> {
+    let account = accounts.run(move |accounts| accounts.register(&name, &password)).await.map_err(IntoResponse::into_response)?;
+
+    Ok(auth.issue_account_token(account.id, account.name))
+}
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    pub name: String,
+    pub password: String,
+}
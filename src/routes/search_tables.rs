@@ -10,24 +10,24 @@ pub async fn search_tables(
 ) -> Json<Vec<PubTable>> {
     let mut response = Vec::new();
 
-    for (id, table) in engine.lock().unwrap().get_tables().iter().enumerate() {
+    for (&id, table) in engine.lock().unwrap().get_tables().iter() {
         let name = table.name.clone();
         let max_players = table.max_players;
-        let minimal_bid = table.minimal_bid;
+        let big_blind = table.big_blind;
         let starting_chips = table.starting_chips;
         let mut current_players = 0;
 
         for player in table.players.clone() {
-            if let Some(_) = player { current_players += 1; }
+            if player.is_some() { current_players += 1; }
         }
 
         if (max_players == criteria.max_players || criteria.max_players == 0)
-        && (minimal_bid == criteria.minimal_bid || criteria.minimal_bid == 0)
+        && (big_blind == criteria.big_blind || criteria.big_blind == 0)
         && (starting_chips == criteria.starting_chips || criteria.starting_chips == 0)
         && (current_players == criteria.current_players || criteria.current_players == 0)
-        && (name == criteria.name || criteria.name == "".to_string())
+        && (name == criteria.name || criteria.name.is_empty())
         {
-            response.push(PubTable { id, name: table.name.clone(), current_players, max_players, minimal_bid, starting_chips });
+            response.push(PubTable { id, name: table.name.clone(), current_players, max_players, small_blind: table.small_blind, big_blind, starting_chips });
             continue;
         }
     }
@@ -38,11 +38,12 @@ pub async fn search_tables(
 
 #[derive(Serialize)]
 pub struct PubTable {
-    id: usize,
+    id: u64,
     name: String,
     current_players: i32,
     max_players: usize,
-    minimal_bid: i32,
+    small_blind: i32,
+    big_blind: i32,
     starting_chips: i32,
 }
 
@@ -51,6 +52,6 @@ pub struct SearchCriteria {
     name: String,
     max_players: usize,
     current_players: i32,
-    minimal_bid: i32,
+    big_blind: i32,
     starting_chips: i32,
 }
\ No newline at end of file
@@ -0,0 +1,30 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Deserialize;
+use crate::{auth::Claims, engine::Engine};
+
+/// Longest accepted client seed, in bytes
+const MAX_CLIENT_SEED_LENGTH: usize = 256;
+
+/// Sets the seed mixed into the next provably fair shuffles, an empty seed removes it
+pub async fn set_client_seed(
+    State(engine): State<Arc<Mutex<Engine>>>,
+    claims: Claims,
+    Json(payload): Json<SeedRequest>,
+) -> StatusCode {
+    if payload.seed.len() > MAX_CLIENT_SEED_LENGTH { return StatusCode::BAD_REQUEST; }
+
+    let mut engine = engine.lock().unwrap();
+    let Some((table, index)) = engine.get_player_table(&claims.key) else { return StatusCode::UNAUTHORIZED; };
+    let Some(player) = &mut table.players[index] else { return StatusCode::UNAUTHORIZED; };
+
+    player.client_seed = Some(payload.seed).filter(|seed| !seed.is_empty());
+
+    StatusCode::ACCEPTED
+}
+
+#[derive(Deserialize)]
+pub struct SeedRequest {
+    seed: String,
+}
@@ -0,0 +1,17 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}};
+use crate::{auth::Claims, engine::Engine};
+
+/// Deals the player back in from the next hand
+pub async fn sit_in(
+    State(engine): State<Arc<Mutex<Engine>>>,
+    claims: Claims,
+) -> Result<StatusCode, Response> {
+    let mut engine = engine.lock().unwrap();
+    let Some((table, seat)) = engine.get_player_table(&claims.key) else { return Err(StatusCode::UNAUTHORIZED.into_response()); };
+
+    table.sit_in(seat).map_err(IntoResponse::into_response)?;
+
+    Ok(StatusCode::ACCEPTED)
+}
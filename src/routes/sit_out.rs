@@ -0,0 +1,17 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}};
+use crate::{auth::Claims, engine::Engine};
+
+/// Keeps the player's seat and chips while they take a break from the following hands
+pub async fn sit_out(
+    State(engine): State<Arc<Mutex<Engine>>>,
+    claims: Claims,
+) -> Result<StatusCode, Response> {
+    let mut engine = engine.lock().unwrap();
+    let Some((table, seat)) = engine.get_player_table(&claims.key) else { return Err(StatusCode::UNAUTHORIZED.into_response()); };
+
+    table.sit_out(seat).map_err(IntoResponse::into_response)?;
+
+    Ok(StatusCode::ACCEPTED)
+}
@@ -1,31 +1,20 @@
 use std::sync::{Arc, Mutex};
 
-use axum::{extract::State, http::StatusCode, Json};
-use crate::engine::Engine;
-
-use super::get_table::Key;
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}};
+use crate::{auth::Claims, engine::Engine};
 
 pub async fn start_game(
     State(engine): State<Arc<Mutex<Engine>>>,
-    Json(Key { key }): Json<Key>
-) -> StatusCode {
+    claims: Claims,
+) -> Result<StatusCode, Response> {
     let mut engine = engine.lock().unwrap();
-
-    for table in engine.get_tables().iter_mut() {
-        for (i, player) in table.players.clone().into_iter().enumerate() {
-            if let Some(player) = player {
-                if player.key == key {
-                    if i != 0 {
-                        return StatusCode::UNAUTHORIZED;
-                    }
-
-                    let result = table.start_new_game();
-                    if !result { return StatusCode::TOO_EARLY; }
-                }
-            }
-        }
+    let Some((table, seat)) = engine.get_player_table(&claims.key) else { return Err(StatusCode::UNAUTHORIZED.into_response()); };
+    // The player in the first occupied seat hosts the table
+    if table.players.iter().position(Option::is_some) != Some(seat) {
+        return Err(StatusCode::UNAUTHORIZED.into_response());
     }
 
+    table.start_new_game().map_err(IntoResponse::into_response)?;
 
-    StatusCode::ACCEPTED
+    Ok(StatusCode::ACCEPTED)
 }
\ No newline at end of file
@@ -0,0 +1,30 @@
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use crate::engine::{card::Card, fairness::{commitment, shuffled_deck, ClientSeed}};
+
+/// Redoes the shuffle of a provably fair hand from the seeds revealed in its history.
+/// Doesn't need a token, anyone holding the seeds can check them.
+pub async fn verify_shuffle(
+    Json(payload): Json<VerifyRequest>,
+) -> Json<VerifyResponse> {
+    Json(VerifyResponse {
+        valid: commitment(&payload.server_seed) == payload.commitment,
+        deck: shuffled_deck(&payload.server_seed, &payload.client_seeds),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct VerifyRequest {
+    server_seed: String,
+    commitment: String,
+    /// Seats and seeds as recorded in the hand history
+    client_seeds: Vec<ClientSeed>,
+}
+
+#[derive(Serialize)]
+pub struct VerifyResponse {
+    /// Whether the server seed matches the commitment published before the hand
+    valid: bool,
+    /// Deck order, the board is dealt from the top five cards and seat `i` gets cards `5 + 2i` and `6 + 2i`
+    deck: Vec<Card>,
+}
@@ -0,0 +1,54 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::{ws::{Message, WebSocket, WebSocketUpgrade}, Query, State}, http::StatusCode, response::Response};
+use serde::Deserialize;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use crate::{auth::{Auth, Claims}, engine::Engine};
+
+use super::get_table::get_pub_table;
+
+pub async fn watch_table(
+    ws: WebSocketUpgrade,
+    State(engine): State<Arc<Mutex<Engine>>>,
+    State(auth): State<Arc<Auth>>,
+    Query(TokenQuery { token }): Query<TokenQuery>,
+) -> Result<Response, StatusCode> {
+    // Browsers can't set headers on WebSocket requests so the token comes in the query string
+    let key = auth.verify_token::<Claims>(&token).ok_or(StatusCode::UNAUTHORIZED)?.key;
+    let updates = engine.lock().unwrap().get_player_table(&key)
+        .map(|(table, _)| table.subscribe())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    Ok(ws.on_upgrade(move |socket| send_updates(socket, engine, key, updates)))
+}
+
+/// Pushes the player's view of the table on connect and after every change.
+/// Any message from the client also triggers a fresh push.
+async fn send_updates(mut socket: WebSocket, engine: Arc<Mutex<Engine>>, key: String, mut updates: Receiver<()>) {
+    loop {
+        let pub_table = engine.lock().unwrap().get_player_table(&key).and_then(|(table, _)| get_pub_table(table, &key));
+        let Some(pub_table) = pub_table else { break; };
+
+        let text = serde_json::to_string(&pub_table).unwrap();
+        if socket.send(Message::Text(text.into())).await.is_err() { break; }
+
+        tokio::select! {
+            update = updates.recv() => {
+                if let Err(RecvError::Closed) = update { break; }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+    }
+
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: String,
+}
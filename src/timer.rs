@@ -0,0 +1,29 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use crate::{accounts::Accounts, engine::Engine};
+
+/// How often the tables are checked for expired turns
+const TICK: Duration = Duration::from_millis(250);
+/// How often the chips on the tables are saved to the accounts database
+const STACK_SYNC: Duration = Duration::from_secs(5);
+
+/// Background task checking or folding for the players who let their turn timer run out
+pub async fn run_turn_timer(engine: Arc<Mutex<Engine>>) {
+    let mut interval = tokio::time::interval(TICK);
+    loop {
+        interval.tick().await;
+        engine.lock().unwrap().handle_timeouts();
+    }
+}
+
+/// Background task saving the stacks on the tables, refunded on the next start if the server stops
+pub async fn run_stack_sync(engine: Arc<Mutex<Engine>>, accounts: Arc<Accounts>) {
+    let mut interval = tokio::time::interval(STACK_SYNC);
+    loop {
+        interval.tick().await;
+        let stacks = engine.lock().unwrap().open_stacks();
+        if let Err(error) = accounts.run(move |accounts| accounts.update_stacks(&stacks)).await {
+            println!("Failed to save the open stacks: {}", error);
+        }
+    }
+}